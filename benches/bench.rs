@@ -1,8 +1,6 @@
-use std::alloc::{alloc_zeroed, dealloc, Layout};
-
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use rand::RngCore;
-use sbbf_rs::{FilterFn, ALIGNMENT, BUCKET_SIZE};
+use sbbf_rs::{FilterFn, Sbbf, StaticFilterFn, ALIGNMENT, BUCKET_SIZE};
 
 mod parquet_impl;
 
@@ -15,7 +13,7 @@ fn benchmark_insert(c: &mut Criterion) {
     c.bench_function("parquet2 insert", |b| {
         let mut rng = rand::thread_rng();
 
-        let mut filter = Filter::new(BITS_PER_KEY, NUM_KEYS);
+        let mut filter = new_filter(BITS_PER_KEY, NUM_KEYS);
         for _ in 0..NUM_KEYS {
             filter.insert(rng.next_u64() % KEY_RANGE);
         }
@@ -24,7 +22,7 @@ fn benchmark_insert(c: &mut Criterion) {
         b.iter(|| {
             let res =
                 parquet2::bloom_filter::is_in_set(black_box(filter.as_bytes()), black_box(num));
-            parquet2::bloom_filter::insert(black_box(filter.as_mut()), black_box(num));
+            parquet2::bloom_filter::insert(black_box(filter.as_bytes_mut()), black_box(num));
             res
         })
     });
@@ -32,11 +30,11 @@ fn benchmark_insert(c: &mut Criterion) {
     c.bench_function("parquet insert", |b| {
         let mut rng = rand::thread_rng();
 
-        let mut filter = Filter::new(BITS_PER_KEY, NUM_KEYS);
+        let mut filter = new_filter(BITS_PER_KEY, NUM_KEYS);
         for _ in 0..NUM_KEYS {
             filter.insert(rng.next_u64() % KEY_RANGE);
         }
-        let mut filter = parquet_impl::Sbbf::new(filter.as_mut());
+        let mut filter = parquet_impl::Sbbf::new(filter.as_bytes_mut());
 
         let num = rng.next_u64();
         b.iter(|| {
@@ -50,7 +48,7 @@ fn benchmark_insert(c: &mut Criterion) {
     c.bench_function("sbbf-rs insert", |b| {
         let mut rng = rand::thread_rng();
 
-        let mut filter = Filter::new(BITS_PER_KEY, NUM_KEYS);
+        let mut filter = new_filter(BITS_PER_KEY, NUM_KEYS);
         for _ in 0..NUM_KEYS {
             filter.insert(rng.next_u64() % KEY_RANGE);
         }
@@ -64,7 +62,7 @@ fn benchmark_contains(c: &mut Criterion) {
     c.bench_function("parquet2 contains", |b| {
         let mut rng = rand::thread_rng();
 
-        let mut filter = Filter::new(8, NUM_KEYS);
+        let mut filter = new_filter(8, NUM_KEYS);
         for _ in 0..NUM_KEYS {
             filter.insert(rng.next_u64() % KEY_RANGE);
         }
@@ -77,12 +75,12 @@ fn benchmark_contains(c: &mut Criterion) {
     c.bench_function("parquet contains", |b| {
         let mut rng = rand::thread_rng();
 
-        let mut filter = Filter::new(BITS_PER_KEY, NUM_KEYS);
+        let mut filter = new_filter(BITS_PER_KEY, NUM_KEYS);
         for _ in 0..NUM_KEYS {
             filter.insert(rng.next_u64() % KEY_RANGE);
         }
 
-        let filter = parquet_impl::Sbbf::new(filter.as_mut());
+        let filter = parquet_impl::Sbbf::new(filter.as_bytes_mut());
 
         let num = rng.next_u64();
 
@@ -92,7 +90,7 @@ fn benchmark_contains(c: &mut Criterion) {
     c.bench_function("sbbf-rs contains", |b| {
         let mut rng = rand::thread_rng();
 
-        let mut filter = Filter::new(8, NUM_KEYS);
+        let mut filter = new_filter(8, NUM_KEYS);
         for _ in 0..NUM_KEYS {
             filter.insert(rng.next_u64() % KEY_RANGE);
         }
@@ -103,69 +101,165 @@ fn benchmark_contains(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, benchmark_insert, benchmark_contains,);
-criterion_main!(benches);
+fn benchmark_contains_batch(c: &mut Criterion) {
+    const BATCH_SIZE: usize = 4096;
 
-struct Filter {
-    filter_fn: FilterFn,
-    buf: Buf,
-    num_buckets: usize,
-}
+    let mut rng = rand::thread_rng();
+    let hashes = (0..BATCH_SIZE).map(|_| rng.next_u64()).collect::<Vec<_>>();
 
-impl Filter {
-    #[inline(always)]
-    fn new(bits_per_key: usize, num_keys: usize) -> Self {
-        let len = bits_per_key * num_keys / 8;
-        let len = ((len + ALIGNMENT / 2) / ALIGNMENT) * ALIGNMENT;
-        Self {
-            filter_fn: FilterFn::new(),
-            buf: Buf::new(len),
-            num_buckets: len / BUCKET_SIZE,
+    // big filters are bound by memory, small ones stay in cache
+    for (name, num_keys) in [("big", NUM_KEYS), ("small", 10_000)] {
+        let mut filter = new_filter(BITS_PER_KEY, num_keys);
+        for _ in 0..num_keys {
+            filter.insert(rng.next_u64());
         }
-    }
 
-    #[inline(always)]
-    fn contains(&self, hash: u64) -> bool {
-        unsafe {
-            self.filter_fn
-                .contains(self.buf.ptr, self.num_buckets, hash)
-        }
+        let mut group = c.benchmark_group(format!("contains_batch/{}", name));
+
+        group.bench_function("sbbf-rs contains loop", |b| {
+            let mut out = vec![false; BATCH_SIZE];
+            b.iter(|| {
+                for (out, &hash) in out.iter_mut().zip(black_box(&hashes)) {
+                    *out = filter.contains(hash);
+                }
+                black_box(&out);
+            })
+        });
+
+        group.bench_function("sbbf-rs contains_batch", |b| {
+            let mut out = vec![false; BATCH_SIZE];
+            b.iter(|| {
+                filter.contains_many(black_box(&hashes), &mut out);
+                black_box(&out);
+            })
+        });
+
+        group.bench_function("sbbf-rs contains_batch_bitmask", |b| {
+            let mut out = vec![0u64; BATCH_SIZE / 64];
+            b.iter(|| {
+                filter.contains_many_bitmask(black_box(&hashes), &mut out);
+                black_box(&out);
+            })
+        });
+
+        group.finish();
     }
+}
 
-    #[inline(always)]
-    fn insert(&mut self, hash: u64) {
-        unsafe { self.filter_fn.insert(self.buf.ptr, self.num_buckets, hash) };
-    }
+fn benchmark_insert_many(c: &mut Criterion) {
+    const BATCH_SIZE: usize = 1 << 20;
 
-    #[inline(always)]
-    fn as_mut(&mut self) -> &mut [u8] {
-        unsafe { std::slice::from_raw_parts_mut(self.buf.ptr, self.buf.layout.size()) }
-    }
+    let mut rng = rand::thread_rng();
+    let hashes = (0..BATCH_SIZE).map(|_| rng.next_u64()).collect::<Vec<_>>();
 
-    #[inline(always)]
-    fn as_bytes(&self) -> &[u8] {
-        unsafe { std::slice::from_raw_parts(self.buf.ptr, self.buf.layout.size()) }
-    }
-}
+    let mut group = c.benchmark_group("build");
+    group.sample_size(10);
+
+    group.bench_function("sbbf-rs insert loop", |b| {
+        let mut filter = new_filter(BITS_PER_KEY, NUM_KEYS);
+        b.iter(|| {
+            let mut num_present = 0;
+            for &hash in black_box(&hashes) {
+                num_present += filter.insert(hash) as usize;
+            }
+            num_present
+        })
+    });
+
+    group.bench_function("sbbf-rs insert_many", |b| {
+        let mut filter = new_filter(BITS_PER_KEY, NUM_KEYS);
+        b.iter(|| filter.insert_many(black_box(&hashes)))
+    });
 
-struct Buf {
-    ptr: *mut u8,
-    layout: Layout,
+    group.finish();
 }
 
-impl Buf {
-    fn new(len: usize) -> Self {
-        let layout = Layout::from_size_align(len, ALIGNMENT).unwrap();
-        let ptr = unsafe { alloc_zeroed(layout) };
+fn benchmark_dispatch(c: &mut Criterion) {
+    const BATCH_SIZE: usize = 4096;
+    const NUM_KEYS: usize = 10_000;
 
-        Self { layout, ptr }
+    let mut rng = rand::thread_rng();
+    let hashes = (0..BATCH_SIZE).map(|_| rng.next_u64()).collect::<Vec<_>>();
+
+    // small filter that stays in cache, so the cost of the call itself shows
+    let mut filter = new_filter(BITS_PER_KEY, NUM_KEYS);
+    for _ in 0..NUM_KEYS {
+        filter.insert(rng.next_u64());
     }
+    let num_buckets = filter.num_buckets();
+
+    let dynamic = FilterFn::new();
+    let fixed = StaticFilterFn;
+
+    let mut group = c.benchmark_group("dispatch");
+
+    group.bench_function(format!("FilterFn contains ({})", dynamic.backend()), |b| {
+        let buf = filter.as_bytes().as_ptr();
+        b.iter(|| {
+            let mut num_present = 0;
+            for &hash in black_box(&hashes) {
+                num_present += unsafe { dynamic.contains(buf, num_buckets, hash) } as usize;
+            }
+            num_present
+        })
+    });
+
+    group.bench_function(
+        format!("StaticFilterFn contains ({})", fixed.backend()),
+        |b| {
+            let buf = filter.as_bytes().as_ptr();
+            b.iter(|| {
+                let mut num_present = 0;
+                for &hash in black_box(&hashes) {
+                    num_present += unsafe { fixed.contains(buf, num_buckets, hash) } as usize;
+                }
+                num_present
+            })
+        },
+    );
+
+    group.bench_function(format!("FilterFn insert ({})", dynamic.backend()), |b| {
+        let mut filter = new_filter(BITS_PER_KEY, NUM_KEYS);
+        let buf = filter.as_bytes_mut().as_mut_ptr();
+        b.iter(|| {
+            let mut num_present = 0;
+            for &hash in black_box(&hashes) {
+                num_present += unsafe { dynamic.insert(buf, num_buckets, hash) } as usize;
+            }
+            num_present
+        })
+    });
+
+    group.bench_function(
+        format!("StaticFilterFn insert ({})", fixed.backend()),
+        |b| {
+            let mut filter = new_filter(BITS_PER_KEY, NUM_KEYS);
+            let buf = filter.as_bytes_mut().as_mut_ptr();
+            b.iter(|| {
+                let mut num_present = 0;
+                for &hash in black_box(&hashes) {
+                    num_present += unsafe { fixed.insert(buf, num_buckets, hash) } as usize;
+                }
+                num_present
+            })
+        },
+    );
+
+    group.finish();
 }
 
-impl Drop for Buf {
-    fn drop(&mut self) {
-        unsafe {
-            dealloc(self.ptr, self.layout);
-        }
-    }
+criterion_group!(
+    benches,
+    benchmark_insert,
+    benchmark_contains,
+    benchmark_contains_batch,
+    benchmark_insert_many,
+    benchmark_dispatch,
+);
+criterion_main!(benches);
+
+fn new_filter(bits_per_key: usize, num_keys: usize) -> Sbbf {
+    let len = bits_per_key * num_keys / 8;
+    let len = ((len + ALIGNMENT / 2) / ALIGNMENT) * ALIGNMENT;
+    Sbbf::new(len / BUCKET_SIZE)
 }
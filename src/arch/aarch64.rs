@@ -1,10 +1,12 @@
 use super::SALT;
 use core::arch::aarch64::{
-    uint32x4_t, vandq_u32, vceqq_u32, vld1q_dup_u32, vld1q_u32, vminvq_u32, vmulq_u32, vorrq_u32,
-    vreinterpretq_s32_u32, vshlq_u32, vshrq_n_u32, vst1q_u32,
+    uint32x4_t, vaddlvq_u8, vandq_u32, vandq_u8, vbicq_u8, vceqq_u32, vcntq_u8, vld1q_dup_u32,
+    vld1q_u32, vld1q_u8, vmaxvq_u8, vminvq_u32, vmulq_u32, vorrq_u32, vorrq_u8,
+    vreinterpretq_s32_u32, vreinterpretq_u32_u8, vreinterpretq_u8_u32, vshlq_u32, vshrq_n_u32,
+    vst1q_u32, vst1q_u8,
 };
 
-use crate::FilterImpl;
+use crate::{Backend, FilterImpl};
 
 pub struct NeonFilter;
 
@@ -34,16 +36,56 @@ impl NeonFilter {
     unsafe fn check(mask: uint32x4_t, bucket: uint32x4_t) -> bool {
         Self::is_eq(mask, vandq_u32(mask, bucket))
     }
+
+    #[target_feature(enable = "neon")]
+    #[inline]
+    unsafe fn load_unaligned(ptr: *const u8) -> uint32x4_t {
+        vreinterpretq_u32_u8(vld1q_u8(ptr))
+    }
+
+    #[target_feature(enable = "neon")]
+    #[inline]
+    unsafe fn check_unaligned(bucket: *const u8, hash: u32) -> bool {
+        let mask = Self::make_mask(hash);
+
+        let bucket = (
+            Self::load_unaligned(bucket),
+            Self::load_unaligned(bucket.add(16)),
+        );
+
+        Self::check(mask.0, bucket.0) && Self::check(mask.1, bucket.1)
+    }
+
+    #[target_feature(enable = "neon")]
+    #[inline]
+    unsafe fn insert_unaligned_bucket(bucket: *mut u8, hash: u32) -> bool {
+        let mask = Self::make_mask(hash);
+        let val = (
+            Self::load_unaligned(bucket),
+            Self::load_unaligned(bucket.add(16)),
+        );
+        let res = Self::check(mask.0, val.0) && Self::check(mask.1, val.1);
+        let c = (vorrq_u32(val.0, mask.0), vorrq_u32(val.1, mask.1));
+        Self::store_unaligned(bucket, c.0);
+        Self::store_unaligned(bucket.add(16), c.1);
+
+        res
+    }
+
+    #[target_feature(enable = "neon")]
+    #[inline]
+    unsafe fn store_unaligned(ptr: *mut u8, val: uint32x4_t) {
+        vst1q_u8(ptr, vreinterpretq_u8_u32(val))
+    }
 }
 
 impl FilterImpl for NeonFilter {
     #[target_feature(enable = "neon")]
     #[inline]
     unsafe fn contains(&self, buf: *const u8, num_buckets: usize, hash: u64) -> bool {
-        let bucket_idx =
-            fastrange_rs::fastrange_32(hash.rotate_left(32) as u32, num_buckets as u32);
+        let bucket_idx = super::bucket_index(num_buckets, hash);
         let mask = Self::make_mask(hash as u32);
-        let bucket = (buf as *const u32).add((bucket_idx * 8) as usize);
+        let bucket = (buf as *const u32).add(bucket_idx * 8);
 
         let bucket = (vld1q_u32(bucket), vld1q_u32(bucket.add(4)));
 
@@ -52,10 +94,9 @@ impl FilterImpl for NeonFilter {
     #[target_feature(enable = "neon")]
     #[inline]
     unsafe fn insert(&self, buf: *mut u8, num_buckets: usize, hash: u64) -> bool {
-        let bucket_idx =
-            fastrange_rs::fastrange_32(hash.rotate_left(32) as u32, num_buckets as u32);
+        let bucket_idx = super::bucket_index(num_buckets, hash);
         let mask = Self::make_mask(hash as u32);
-        let bucket = (buf as *mut u32).add((bucket_idx * 8) as usize);
+        let bucket = (buf as *mut u32).add(bucket_idx * 8);
         let val = (vld1q_u32(bucket), vld1q_u32(bucket.add(4)));
         let res = Self::check(mask.0, val.0) && Self::check(mask.1, val.1);
         let c = (vorrq_u32(val.0, mask.0), vorrq_u32(val.1, mask.1));
@@ -64,7 +105,107 @@ impl FilterImpl for NeonFilter {
 
         res
     }
-    fn which(&self) -> &'static str {
-        "NeonFilter"
+    #[target_feature(enable = "neon")]
+    #[inline]
+    unsafe fn contains_unaligned(&self, buf: *const u8, num_buckets: usize, hash: u64) -> bool {
+        let bucket_idx = super::bucket_index(num_buckets, hash);
+        Self::check_unaligned(buf.add(bucket_idx * 32), hash as u32)
+    }
+    #[target_feature(enable = "neon")]
+    #[inline]
+    unsafe fn insert_unaligned(&self, buf: *mut u8, num_buckets: usize, hash: u64) -> bool {
+        let bucket_idx = super::bucket_index(num_buckets, hash);
+        Self::insert_unaligned_bucket(buf.add(bucket_idx * 32), hash as u32)
+    }
+    #[target_feature(enable = "neon")]
+    unsafe fn insert_batch(&self, buf: *mut u8, num_buckets: usize, hashes: &[u64]) -> usize {
+        super::insert_batch(buf, num_buckets, hashes, |offsets, hashes| {
+            super::insert_each(buf, offsets, hashes, |bucket, hash| {
+                Self::insert_unaligned_bucket(bucket, hash)
+            })
+        })
+    }
+    #[target_feature(enable = "neon")]
+    unsafe fn contains_batch(
+        &self,
+        buf: *const u8,
+        num_buckets: usize,
+        hashes: &[u64],
+        out: &mut [bool],
+    ) {
+        super::contains_batch(buf, num_buckets, hashes, out, |offsets, hashes| {
+            super::check_each(buf, offsets, hashes, |bucket, hash| {
+                Self::check_unaligned(bucket, hash)
+            })
+        })
+    }
+    #[target_feature(enable = "neon")]
+    unsafe fn contains_batch_bitmask(
+        &self,
+        buf: *const u8,
+        num_buckets: usize,
+        hashes: &[u64],
+        out: &mut [u64],
+    ) {
+        super::contains_batch_bitmask(buf, num_buckets, hashes, out, |offsets, hashes| {
+            super::check_each(buf, offsets, hashes, |bucket, hash| {
+                Self::check_unaligned(bucket, hash)
+            })
+        })
+    }
+    #[target_feature(enable = "neon")]
+    unsafe fn union(&self, dst: *mut u8, src: *const u8, num_buckets: usize) {
+        for i in 0..num_buckets * 2 {
+            let dst = dst.add(i * 16);
+            let src = src.add(i * 16);
+            vst1q_u8(dst, vorrq_u8(vld1q_u8(dst), vld1q_u8(src)));
+        }
+    }
+    #[target_feature(enable = "neon")]
+    unsafe fn intersect(&self, dst: *mut u8, src: *const u8, num_buckets: usize) {
+        for i in 0..num_buckets * 2 {
+            let dst = dst.add(i * 16);
+            let src = src.add(i * 16);
+            vst1q_u8(dst, vandq_u8(vld1q_u8(dst), vld1q_u8(src)));
+        }
+    }
+    #[target_feature(enable = "neon")]
+    unsafe fn is_subset(&self, a: *const u8, b: *const u8, num_buckets: usize) -> bool {
+        (0..num_buckets * 2).all(|i| {
+            // bits of `a` that aren't in `b`
+            vmaxvq_u8(vbicq_u8(vld1q_u8(a.add(i * 16)), vld1q_u8(b.add(i * 16)))) == 0
+        })
+    }
+    #[target_feature(enable = "neon")]
+    unsafe fn contains_any(
+        &self,
+        buf: *const u8,
+        num_buckets: usize,
+        num_filters: usize,
+        hash: u64,
+    ) -> bool {
+        let mask = Self::make_mask(hash as u32);
+        super::contains_any(buf, num_buckets, num_filters, hash, |bucket| {
+            Self::check(mask.0, Self::load_unaligned(bucket))
+                && Self::check(mask.1, Self::load_unaligned(bucket.add(16)))
+        })
+    }
+    #[target_feature(enable = "neon")]
+    unsafe fn count_ones(&self, buf: *const u8, num_buckets: usize) -> u64 {
+        (0..num_buckets * 2)
+            .map(|i| u64::from(vaddlvq_u8(vcntq_u8(vld1q_u8(buf.add(i * 16))))))
+            .sum()
+    }
+    #[target_feature(enable = "neon")]
+    unsafe fn count_ones_union(&self, a: *const u8, b: *const u8, num_buckets: usize) -> u64 {
+        (0..num_buckets * 2)
+            .map(|i| {
+                let val = vorrq_u8(vld1q_u8(a.add(i * 16)), vld1q_u8(b.add(i * 16)));
+                u64::from(vaddlvq_u8(vcntq_u8(val)))
+            })
+            .sum()
+    }
+    fn backend(&self) -> Backend {
+        Backend::Neon
     }
 }
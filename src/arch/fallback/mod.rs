@@ -1,58 +1,134 @@
-use crate::FilterImpl;
+use crate::{Backend, FilterImpl};
 
-mod parquet_impl;
+pub(super) mod parquet_impl;
 
 pub struct FallbackFilter;
 
 impl FilterImpl for FallbackFilter {
+    #[inline]
     unsafe fn contains(&self, buf: *const u8, num_buckets: usize, hash: u64) -> bool {
         parquet_impl::check_hash(buf, num_buckets, hash)
     }
+    #[inline]
     unsafe fn insert(&self, buf: *mut u8, num_buckets: usize, hash: u64) -> bool {
         parquet_impl::insert_hash(buf, num_buckets, hash)
     }
-    fn which(&self) -> &'static str {
-        "FallbackFilter"
+    #[inline]
+    unsafe fn contains_unaligned(&self, buf: *const u8, num_buckets: usize, hash: u64) -> bool {
+        parquet_impl::check_hash(buf, num_buckets, hash)
     }
-}
-
-#[cfg(test)]
-mod test {
-    use super::*;
-    use crate::ALIGNMENT;
-    use std::alloc::{alloc_zeroed, dealloc, Layout};
-
-    struct Buf {
-        ptr: *mut u8,
-        layout: Layout,
+    #[inline]
+    unsafe fn insert_unaligned(&self, buf: *mut u8, num_buckets: usize, hash: u64) -> bool {
+        parquet_impl::insert_hash(buf, num_buckets, hash)
     }
-
-    impl Buf {
-        fn new(len: usize) -> Self {
-            let layout = Layout::from_size_align(len, ALIGNMENT).unwrap();
-            let ptr = unsafe { alloc_zeroed(layout) };
-
-            Self { layout, ptr }
+    #[inline]
+    unsafe fn contains_batch(
+        &self,
+        buf: *const u8,
+        num_buckets: usize,
+        hashes: &[u64],
+        out: &mut [bool],
+    ) {
+        super::contains_batch(buf, num_buckets, hashes, out, |offsets, hashes| {
+            super::check_each(buf, offsets, hashes, |bucket, hash| {
+                parquet_impl::check_bucket(bucket, hash)
+            })
+        })
+    }
+    #[inline]
+    unsafe fn insert_batch(&self, buf: *mut u8, num_buckets: usize, hashes: &[u64]) -> usize {
+        super::insert_batch(buf, num_buckets, hashes, |offsets, hashes| {
+            super::insert_each(buf, offsets, hashes, |bucket, hash| {
+                parquet_impl::insert_bucket(bucket, hash)
+            })
+        })
+    }
+    #[inline]
+    unsafe fn contains_batch_bitmask(
+        &self,
+        buf: *const u8,
+        num_buckets: usize,
+        hashes: &[u64],
+        out: &mut [u64],
+    ) {
+        super::contains_batch_bitmask(buf, num_buckets, hashes, out, |offsets, hashes| {
+            super::check_each(buf, offsets, hashes, |bucket, hash| {
+                parquet_impl::check_bucket(bucket, hash)
+            })
+        })
+    }
+    #[inline]
+    unsafe fn union(&self, dst: *mut u8, src: *const u8, num_buckets: usize) {
+        for i in 0..num_buckets * 4 {
+            let dst = (dst as *mut u64).add(i);
+            let src = (src as *const u64).add(i);
+            dst.write_unaligned(dst.read_unaligned() | src.read_unaligned());
         }
     }
-
-    impl Drop for Buf {
-        fn drop(&mut self) {
-            unsafe {
-                dealloc(self.ptr, self.layout);
-            }
+    #[inline]
+    unsafe fn intersect(&self, dst: *mut u8, src: *const u8, num_buckets: usize) {
+        for i in 0..num_buckets * 4 {
+            let dst = (dst as *mut u64).add(i);
+            let src = (src as *const u64).add(i);
+            dst.write_unaligned(dst.read_unaligned() & src.read_unaligned());
         }
     }
+    #[inline]
+    unsafe fn is_subset(&self, a: *const u8, b: *const u8, num_buckets: usize) -> bool {
+        (0..num_buckets * 4).all(|i| {
+            let a = (a as *const u64).add(i).read_unaligned();
+            let b = (b as *const u64).add(i).read_unaligned();
+            a & !b == 0
+        })
+    }
+    #[inline]
+    unsafe fn contains_any(
+        &self,
+        buf: *const u8,
+        num_buckets: usize,
+        num_filters: usize,
+        hash: u64,
+    ) -> bool {
+        let mask = super::bucket_mask(hash as u32);
+        super::contains_any(buf, num_buckets, num_filters, hash, |bucket| {
+            parquet_impl::check_bucket_mask(bucket, mask)
+        })
+    }
+    #[inline]
+    unsafe fn count_ones(&self, buf: *const u8, num_buckets: usize) -> u64 {
+        (0..num_buckets * 4)
+            .map(|i| u64::from((buf as *const u64).add(i).read_unaligned().count_ones()))
+            .sum()
+    }
+    #[inline]
+    unsafe fn count_ones_union(&self, a: *const u8, b: *const u8, num_buckets: usize) -> u64 {
+        (0..num_buckets * 4)
+            .map(|i| {
+                let a = (a as *const u64).add(i).read_unaligned();
+                let b = (b as *const u64).add(i).read_unaligned();
+                u64::from((a | b).count_ones())
+            })
+            .sum()
+    }
+    fn backend(&self) -> Backend {
+        Backend::Fallback
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod test {
+    use super::*;
+    use crate::Sbbf;
 
     #[test]
     fn smoke_test_fallback() {
+        let mut filter = Sbbf::new(2);
+        let buf = filter.as_bytes_mut().as_mut_ptr();
         unsafe {
-            let buf = Buf::new(64);
-
-            assert!(!FallbackFilter.insert(buf.ptr, 2, 69));
-            assert!(FallbackFilter.contains(buf.ptr, 2, 69));
-            assert!(!FallbackFilter.contains(buf.ptr, 2, 12));
-            assert!(FallbackFilter.insert(buf.ptr, 2, 69));
+            assert!(!FallbackFilter.insert(buf, 2, 69));
+            assert!(FallbackFilter.contains(buf, 2, 69));
+            assert!(!FallbackFilter.contains(buf, 2, 12));
+            assert!(FallbackFilter.insert(buf, 2, 69));
         }
     }
 }
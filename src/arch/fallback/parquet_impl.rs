@@ -92,7 +92,12 @@ impl Block {
     /// returns true when every bit that is set in the result of mask is also set in the block.
     #[inline(always)]
     fn check(&self, hash: u32) -> bool {
-        let mask = Self::mask(hash);
+        self.check_mask(&Self::mask(hash))
+    }
+
+    /// returns true when every bit that is set in `mask` is also set in the block.
+    #[inline(always)]
+    fn check_mask(&self, mask: &Self) -> bool {
         for i in 0..8 {
             if self.0[i] & mask.0[i] == 0 {
                 return false;
@@ -145,6 +150,38 @@ pub unsafe fn check_hash(buf: *const u8, num_buckets: usize, hash: u64) -> bool
     block.check(hash as u32)
 }
 
+#[inline(always)]
+pub unsafe fn insert_bucket(bucket: *mut u8, hash: u32) -> bool {
+    let buf = core::slice::from_raw_parts_mut(bucket, 32);
+
+    let mut block = Block::load(buf);
+
+    let res = block.check(hash);
+    block.insert(hash);
+
+    block.store(buf);
+
+    res
+}
+
+#[inline(always)]
+pub unsafe fn check_bucket(bucket: *const u8, hash: u32) -> bool {
+    let buf = core::slice::from_raw_parts(bucket, 32);
+
+    let block = Block::load(buf);
+
+    block.check(hash)
+}
+
+#[inline(always)]
+pub unsafe fn check_bucket_mask(bucket: *const u8, mask: [u32; 8]) -> bool {
+    let buf = core::slice::from_raw_parts(bucket, 32);
+
+    let block = Block::load(buf);
+
+    block.check_mask(&Block(mask))
+}
+
 #[inline(always)]
 fn hash_to_block_index(num_buckets: usize, hash: u64) -> usize {
     // unchecked_mul is unstable, but in reality this is safe, we'd just use saturating mul
@@ -1,6 +1,8 @@
 #[cfg(all(target_arch = "aarch64", target_endian = "little"))]
 mod aarch64;
 mod fallback;
+#[cfg(all(target_arch = "riscv64", target_os = "linux"))]
+mod riscv64;
 #[cfg(all(
     target_family = "wasm",
     target_feature = "simd128",
@@ -10,37 +12,85 @@ mod wasm;
 #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
 mod x86;
 
+use crate::Backend;
+
+#[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+cpufeatures::new!(cpuid_avx512, "avx512f", "avx512vl");
+#[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+cpufeatures::new!(cpuid_avx2, "avx2");
 #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+cpufeatures::new!(cpuid_sse, "sse4.1");
+
+/// Loads the most preferred backend the cpu supports.
 pub(crate) fn load() -> &'static dyn crate::FilterImpl {
-    cpufeatures::new!(cpuid_avx2, "avx2");
-    cpufeatures::new!(cpuid_sse, "sse4.1");
+    Backend::ALL
+        .into_iter()
+        .find_map(load_backend)
+        .unwrap_or(&fallback::FallbackFilter)
+}
 
-    if cpuid_avx2::get() {
-        &x86::Avx2Filter
-    } else if cpuid_sse::get() {
-        &x86::SseFilter
-    } else {
-        &fallback::FallbackFilter
+/// Loads `backend` if it is compiled in and the cpu supports it.
+pub(crate) fn load_backend(backend: Backend) -> Option<&'static dyn crate::FilterImpl> {
+    match backend {
+        #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+        Backend::Avx512 if cpuid_avx512::get() => Some(&x86::Avx512Filter),
+        #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+        Backend::Avx2 if cpuid_avx2::get() => Some(&x86::Avx2Filter),
+        #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+        Backend::Sse41 if cpuid_sse::get() => Some(&x86::SseFilter),
+        #[cfg(all(target_arch = "aarch64", target_endian = "little"))]
+        Backend::Neon => Some(&aarch64::NeonFilter),
+        #[cfg(all(
+            target_family = "wasm",
+            target_feature = "simd128",
+            target_endian = "little"
+        ))]
+        Backend::Wasm => Some(&wasm::WasmFilter),
+        #[cfg(all(target_arch = "riscv64", target_os = "linux"))]
+        Backend::Rvv if riscv64::has_v() => Some(&riscv64::RvvFilter),
+        Backend::Fallback => Some(&fallback::FallbackFilter),
+        _ => None,
     }
 }
 
+// Backend picked at compile time from the enabled target features, used by
+// StaticFilterFn. Same order of preference as load.
+#[cfg(all(
+    any(target_arch = "x86_64", target_arch = "x86"),
+    target_feature = "avx512f",
+    target_feature = "avx512vl"
+))]
+pub(crate) use x86::Avx512Filter as StaticFilter;
+
+#[cfg(all(
+    any(target_arch = "x86_64", target_arch = "x86"),
+    target_feature = "avx2",
+    not(all(target_feature = "avx512f", target_feature = "avx512vl"))
+))]
+pub(crate) use x86::Avx2Filter as StaticFilter;
+
+#[cfg(all(
+    any(target_arch = "x86_64", target_arch = "x86"),
+    target_feature = "sse4.1",
+    not(target_feature = "avx2")
+))]
+pub(crate) use x86::SseFilter as StaticFilter;
+
 #[cfg(all(target_arch = "aarch64", target_endian = "little"))]
-pub(crate) fn load() -> &'static dyn crate::FilterImpl {
-    &aarch64::NeonFilter
-}
+pub(crate) use aarch64::NeonFilter as StaticFilter;
 
 #[cfg(all(
     target_family = "wasm",
     target_feature = "simd128",
     target_endian = "little"
 ))]
-pub(crate) fn load() -> &'static dyn crate::FilterImpl {
-    &wasm::WasmFilter
-}
+pub(crate) use wasm::WasmFilter as StaticFilter;
 
 #[cfg(not(any(
-    target_arch = "x86_64",
-    target_arch = "x86",
+    all(
+        any(target_arch = "x86_64", target_arch = "x86"),
+        target_feature = "sse4.1"
+    ),
     all(target_arch = "aarch64", target_endian = "little"),
     all(
         target_family = "wasm",
@@ -48,20 +98,771 @@ pub(crate) fn load() -> &'static dyn crate::FilterImpl {
         target_endian = "little"
     )
 )))]
-pub(crate) fn load() -> &'static dyn crate::FilterImpl {
-    &fallback::FallbackFilter
+pub(crate) use fallback::FallbackFilter as StaticFilter;
+
+/// Runs a fixed set of hashes through `filter` and [fallback::parquet_impl] and returns false
+/// if any result or any byte of the filters differs.
+///
+/// Uses scratch buffers on the stack so it works without `alloc`.
+pub(crate) fn self_test(filter: &dyn crate::FilterImpl) -> bool {
+    // Tiny filters first so hashes keep landing in the same bucket and most inserts find only
+    // some of their bits set, which is where an insert that reports the wrong result shows up.
+    // 13 is odd so bucket_index isn't a plain shift.
+    const NUM_BUCKETS: [usize; 3] = [1, 2, 13];
+    const MAX_LEN: usize = 13 * crate::BUCKET_SIZE;
+    const NUM_INSERTS: usize = 64;
+    const NUM_PROBES: usize = 256;
+
+    #[repr(C, align(64))]
+    struct Scratch([u8; MAX_LEN + crate::ALIGNMENT]);
+
+    let mut hashes = [0u64; NUM_INSERTS + NUM_PROBES];
+    for (i, hash) in hashes.iter_mut().enumerate() {
+        *hash = crate::xxh64(&(i as u64).to_le_bytes(), 0);
+    }
+    // all zero and all one bits in both halves of the hash
+    hashes[0] = 0;
+    hashes[1] = u64::MAX;
+    let (inserts, probes) = hashes.split_at(NUM_INSERTS);
+
+    let mut expected = [0u8; MAX_LEN];
+    let mut aligned = Scratch([0; MAX_LEN + crate::ALIGNMENT]);
+    let mut unaligned = Scratch([0; MAX_LEN + crate::ALIGNMENT]);
+    let mut batch = Scratch([0; MAX_LEN + crate::ALIGNMENT]);
+
+    for num_buckets in NUM_BUCKETS {
+        let len = num_buckets * crate::BUCKET_SIZE;
+        let expected = &mut expected[..len];
+        let aligned = &mut aligned.0[..len];
+        let unaligned = &mut unaligned.0[1..len + 1];
+        let batch = &mut batch.0[3..len + 3];
+        for buf in [&mut *expected, &mut *aligned, &mut *unaligned, &mut *batch] {
+            buf.fill(0);
+        }
+
+        unsafe {
+            let mut num_present = 0;
+            for &hash in inserts {
+                let res =
+                    fallback::parquet_impl::insert_hash(expected.as_mut_ptr(), num_buckets, hash);
+                num_present += res as usize;
+                if filter.insert(aligned.as_mut_ptr(), num_buckets, hash) != res
+                    || filter.insert_unaligned(unaligned.as_mut_ptr(), num_buckets, hash) != res
+                {
+                    return false;
+                }
+            }
+            if filter.insert_batch(batch.as_mut_ptr(), num_buckets, inserts) != num_present {
+                return false;
+            }
+            if aligned != expected || unaligned != expected || batch != expected {
+                return false;
+            }
+
+            let ones = expected.iter().map(|b| b.count_ones() as u64).sum::<u64>();
+            if filter.count_ones(unaligned.as_ptr(), num_buckets) != ones {
+                return false;
+            }
+
+            let mut out = [false; NUM_PROBES];
+            let mut bitmask = [0u64; NUM_PROBES / 64];
+            filter.contains_batch(unaligned.as_ptr(), num_buckets, probes, &mut out);
+            filter.contains_batch_bitmask(unaligned.as_ptr(), num_buckets, probes, &mut bitmask);
+            // probes include the inserted hashes so both results show up
+            for (i, &hash) in inserts.iter().chain(probes).enumerate() {
+                let res = fallback::parquet_impl::check_hash(expected.as_ptr(), num_buckets, hash);
+                if filter.contains(aligned.as_ptr(), num_buckets, hash) != res
+                    || filter.contains_unaligned(unaligned.as_ptr(), num_buckets, hash) != res
+                {
+                    return false;
+                }
+                if let Some(i) = i.checked_sub(NUM_INSERTS) {
+                    if out[i] != res || (bitmask[i / 64] >> (i % 64) & 1 == 1) != res {
+                        return false;
+                    }
+                }
+            }
+        }
+    }
+
+    true
 }
 
-#[cfg(any(
-    target_arch = "x86_64",
-    target_arch = "x86",
-    all(target_arch = "aarch64", target_endian = "little"),
-    all(
-        target_family = "wasm",
-        target_feature = "simd128",
-        target_endian = "little"
-    )
-))]
 const SALT: [u32; 8] = [
     0x47b6137b, 0x44974d91, 0x8824ad5b, 0xa2b7289d, 0x705495c7, 0x2df1424b, 0x9efc4947, 0x5c6bfb31,
 ];
+
+/// Returns the words that have to be set in a bucket for `hash`, each one has a single bit set.
+#[inline(always)]
+pub(crate) fn bucket_mask(hash: u32) -> [u32; 8] {
+    SALT.map(|salt| 1 << (hash.wrapping_mul(salt) >> 27))
+}
+
+/// Number of hashes that have their bucket computed and prefetched together in batch methods.
+/// Results of a chunk are collected into a `u16` mask so this can't be bigger than 16.
+const BATCH_CHUNK: usize = 16;
+
+/// Index of the bucket `hash` maps to, every backend computes it using this function.
+///
+/// Only the upper 32 bits of `hash` select the bucket and `num_buckets` is used as a u32, so it
+/// can't be bigger than [MAX_NUM_BUCKETS](crate::MAX_NUM_BUCKETS).
+#[inline(always)]
+pub(crate) fn bucket_index(num_buckets: usize, hash: u64) -> usize {
+    debug_assert!(num_buckets <= crate::MAX_NUM_BUCKETS);
+    fastrange_rs::fastrange_32(hash.rotate_left(32) as u32, num_buckets as u32) as usize
+}
+
+#[inline(always)]
+fn bucket_offset(num_buckets: usize, hash: u64) -> usize {
+    bucket_index(num_buckets, hash) * crate::BUCKET_SIZE
+}
+
+#[inline(always)]
+unsafe fn prefetch(ptr: *const u8) {
+    #[cfg(target_arch = "x86")]
+    core::arch::x86::_mm_prefetch::<{ core::arch::x86::_MM_HINT_T0 }>(ptr as *const i8);
+    #[cfg(target_arch = "x86_64")]
+    core::arch::x86_64::_mm_prefetch::<{ core::arch::x86_64::_MM_HINT_T0 }>(ptr as *const i8);
+    #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+    let _ = ptr;
+}
+
+/// Computes bucket offsets of the hashes in `chunk` into `offsets` and prefetches the buckets.
+#[inline(always)]
+unsafe fn prepare_chunk(
+    buf: *const u8,
+    num_buckets: usize,
+    chunk: &[u64],
+    offsets: &mut [usize; BATCH_CHUNK],
+) {
+    for (offset, &hash) in offsets.iter_mut().zip(chunk) {
+        *offset = bucket_offset(num_buckets, hash);
+        prefetch(buf.add(*offset));
+    }
+}
+
+/// Calls `f` with the index of the first hash, the bucket offsets and the hashes of each chunk
+/// of `hashes`.
+///
+/// Buckets of the next chunk of hashes are computed and prefetched before `f` is called with the
+/// current chunk so the memory accesses overlap.
+#[inline(always)]
+unsafe fn for_each_chunk(
+    buf: *const u8,
+    num_buckets: usize,
+    hashes: &[u64],
+    mut f: impl FnMut(usize, &[usize], &[u64]),
+) {
+    let mut offsets = [0; BATCH_CHUNK];
+    let mut next_offsets = [0; BATCH_CHUNK];
+
+    let mut chunks = hashes.chunks(BATCH_CHUNK).enumerate().peekable();
+    if let Some((_, chunk)) = chunks.peek() {
+        prepare_chunk(buf, num_buckets, chunk, &mut offsets);
+    }
+
+    while let Some((chunk_idx, chunk)) = chunks.next() {
+        if let Some((_, next)) = chunks.peek() {
+            prepare_chunk(buf, num_buckets, next, &mut next_offsets);
+        }
+
+        f(chunk_idx * BATCH_CHUNK, &offsets[..chunk.len()], chunk);
+
+        core::mem::swap(&mut offsets, &mut next_offsets);
+    }
+}
+
+/// Checks each hash against the bucket at the matching offset using `check`.
+/// Returns a mask with bit `i` set if `hashes[i]` is in the filter.
+///
+/// `check` should test the hash against the bucket without requiring the bucket to be aligned.
+#[inline(always)]
+unsafe fn check_each(
+    buf: *const u8,
+    offsets: &[usize],
+    hashes: &[u64],
+    check: impl Fn(*const u8, u32) -> bool,
+) -> u16 {
+    let mut res = 0;
+    for (i, (&offset, &hash)) in offsets.iter().zip(hashes).enumerate() {
+        res |= (check(buf.add(offset), hash as u32) as u16) << i;
+    }
+    res
+}
+
+/// Inserts each hash into the bucket at the matching offset using `insert`.
+/// Returns the number of hashes that were already in the filter.
+///
+/// `insert` should insert the hash into the bucket without requiring the bucket to be aligned
+/// and return true if it was already there.
+#[inline(always)]
+unsafe fn insert_each(
+    buf: *mut u8,
+    offsets: &[usize],
+    hashes: &[u64],
+    insert: impl Fn(*mut u8, u32) -> bool,
+) -> usize {
+    let mut num_present = 0;
+    for (&offset, &hash) in offsets.iter().zip(hashes) {
+        num_present += insert(buf.add(offset), hash as u32) as usize;
+    }
+    num_present
+}
+
+/// Shared implementation of [crate::FilterImpl::contains_batch].
+/// `check_chunk` gets the bucket offsets and the hashes of a chunk and returns a mask like
+/// [check_each] does.
+#[inline(always)]
+unsafe fn contains_batch(
+    buf: *const u8,
+    num_buckets: usize,
+    hashes: &[u64],
+    out: &mut [bool],
+    check_chunk: impl Fn(&[usize], &[u64]) -> u16,
+) {
+    for_each_chunk(buf, num_buckets, hashes, |start, offsets, chunk| {
+        let res = check_chunk(offsets, chunk);
+        let out = out.get_unchecked_mut(start..start + chunk.len());
+        for (i, out) in out.iter_mut().enumerate() {
+            *out = (res >> i) & 1 == 1;
+        }
+    })
+}
+
+/// Shared implementation of [crate::FilterImpl::contains_batch_bitmask].
+/// Same as [contains_batch] but the chunk masks are written into `out` directly.
+#[inline(always)]
+unsafe fn contains_batch_bitmask(
+    buf: *const u8,
+    num_buckets: usize,
+    hashes: &[u64],
+    out: &mut [u64],
+    check_chunk: impl Fn(&[usize], &[u64]) -> u16,
+) {
+    // chunks never cross a word boundary
+    const _: () = assert!(64 % BATCH_CHUNK == 0);
+
+    out.get_unchecked_mut(..hashes.len().div_ceil(64)).fill(0);
+    for_each_chunk(buf, num_buckets, hashes, |start, offsets, chunk| {
+        *out.get_unchecked_mut(start / 64) |=
+            u64::from(check_chunk(offsets, chunk)) << (start % 64);
+    })
+}
+
+/// Shared implementation of [crate::FilterImpl::insert_batch].
+/// `insert_chunk` gets the bucket offsets and the hashes of a chunk and returns the number of
+/// hashes that were already in the filter like [insert_each] does.
+///
+/// Chunks are inserted in order so the result is the same as inserting the hashes one by one,
+/// as long as `insert_chunk` inserts the hashes of a chunk in order.
+#[inline(always)]
+unsafe fn insert_batch(
+    buf: *mut u8,
+    num_buckets: usize,
+    hashes: &[u64],
+    insert_chunk: impl Fn(&[usize], &[u64]) -> usize,
+) -> usize {
+    let mut num_present = 0;
+    for_each_chunk(buf, num_buckets, hashes, |_, offsets, chunk| {
+        num_present += insert_chunk(offsets, chunk);
+    });
+    num_present
+}
+
+/// Shared implementation of [crate::FilterImpl::contains_any].
+/// Calls `check` with the bucket of `hash` in each of the `num_filters` filters stored back to
+/// back in `buf` until one of them returns true.
+/// Backends compute the mask of `hash` once and capture it in `check`.
+#[inline(always)]
+unsafe fn contains_any(
+    buf: *const u8,
+    num_buckets: usize,
+    num_filters: usize,
+    hash: u64,
+    check: impl Fn(*const u8) -> bool,
+) -> bool {
+    let stride = num_buckets * crate::BUCKET_SIZE;
+    let bucket = buf.add(bucket_offset(num_buckets, hash));
+    (0..num_filters).any(|i| check(bucket.add(i * stride)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{FilterImpl, ALIGNMENT, BUCKET_SIZE};
+
+    fn impls() -> Vec<&'static dyn FilterImpl> {
+        #[allow(unused_mut)]
+        let mut impls: Vec<&'static dyn FilterImpl> = vec![&fallback::FallbackFilter];
+        #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+        {
+            if is_x86_feature_detected!("avx512f") && is_x86_feature_detected!("avx512vl") {
+                impls.push(&x86::Avx512Filter);
+            }
+            if is_x86_feature_detected!("avx2") {
+                impls.push(&x86::Avx2Filter);
+            }
+            if is_x86_feature_detected!("sse4.1") {
+                impls.push(&x86::SseFilter);
+            }
+        }
+        #[cfg(all(target_arch = "aarch64", target_endian = "little"))]
+        impls.push(&aarch64::NeonFilter);
+        #[cfg(all(
+            target_family = "wasm",
+            target_feature = "simd128",
+            target_endian = "little"
+        ))]
+        impls.push(&wasm::WasmFilter);
+        #[cfg(all(target_arch = "riscv64", target_os = "linux"))]
+        if riscv64::has_v() {
+            impls.push(&riscv64::RvvFilter);
+        }
+        impls
+    }
+
+    fn hashes(num: u64) -> impl Iterator<Item = u64> {
+        (0..num).map(|i| (i + 1).wrapping_mul(0x9e3779b97f4a7c15))
+    }
+
+    #[test]
+    fn unaligned_matches_aligned() {
+        const NUM_BUCKETS: usize = 7;
+        const LEN: usize = NUM_BUCKETS * BUCKET_SIZE;
+
+        for filter in impls() {
+            let mut expected = vec![0u8; LEN];
+            let mut inserted = Vec::new();
+            for hash in hashes(40) {
+                inserted.push(unsafe {
+                    fallback::FallbackFilter.insert(expected.as_mut_ptr(), NUM_BUCKETS, hash)
+                });
+            }
+
+            for offset in 0..ALIGNMENT {
+                let mut storage = vec![0u8; LEN + 2 * ALIGNMENT];
+                let start = storage.as_ptr().align_offset(ALIGNMENT) + offset;
+                let buf = &mut storage[start..start + LEN];
+
+                for (hash, &was_inserted) in hashes(40).zip(inserted.iter()) {
+                    let res =
+                        unsafe { filter.insert_unaligned(buf.as_mut_ptr(), NUM_BUCKETS, hash) };
+                    assert_eq!(res, was_inserted, "{} offset {}", filter.backend(), offset);
+                }
+                assert_eq!(
+                    buf,
+                    expected.as_slice(),
+                    "{} offset {}",
+                    filter.backend(),
+                    offset
+                );
+
+                for hash in hashes(1000) {
+                    let expected = unsafe {
+                        fallback::FallbackFilter.contains(expected.as_ptr(), NUM_BUCKETS, hash)
+                    };
+                    let res = unsafe { filter.contains_unaligned(buf.as_ptr(), NUM_BUCKETS, hash) };
+                    assert_eq!(res, expected, "{} offset {}", filter.backend(), offset);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn insert_with_half_of_mask_set() {
+        for filter in impls() {
+            for hash in hashes(100) {
+                let mask = bucket_mask(hash as u32);
+                for half in [0..4, 4..8] {
+                    let mut bucket = [0u32; 8];
+                    bucket[half.clone()].copy_from_slice(&mask[half.clone()]);
+                    let bytes = bucket.map(u32::to_le_bytes).concat();
+
+                    let mut storage = [0u8; BUCKET_SIZE + ALIGNMENT + 1];
+                    let start = storage.as_ptr().align_offset(ALIGNMENT);
+                    for offset in [start, start + 1] {
+                        let buf = &mut storage[offset..offset + BUCKET_SIZE];
+                        buf.copy_from_slice(&bytes);
+                        let res = unsafe {
+                            if offset == start {
+                                filter.insert(buf.as_mut_ptr(), 1, hash)
+                            } else {
+                                filter.insert_unaligned(buf.as_mut_ptr(), 1, hash)
+                            }
+                        };
+                        assert!(!res, "{} half {:?}", filter.backend(), half);
+                        assert_eq!(
+                            buf,
+                            mask.map(u32::to_le_bytes).concat(),
+                            "{}",
+                            filter.backend()
+                        );
+
+                        buf.copy_from_slice(&bytes);
+                        let num_present =
+                            unsafe { filter.insert_batch(buf.as_mut_ptr(), 1, &[hash]) };
+                        assert_eq!(num_present, 0, "{} half {:?}", filter.backend(), half);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn batch_matches_single() {
+        const NUM_BUCKETS: usize = 13;
+        const LEN: usize = NUM_BUCKETS * BUCKET_SIZE;
+
+        let mut expected = vec![0u8; LEN];
+        for hash in hashes(60) {
+            unsafe { fallback::FallbackFilter.insert(expected.as_mut_ptr(), NUM_BUCKETS, hash) };
+        }
+        let probes = hashes(1000).collect::<Vec<_>>();
+
+        for filter in impls() {
+            for offset in [0, 1, 7, 32] {
+                let mut storage = vec![0u8; LEN + 2 * ALIGNMENT];
+                let start = storage.as_ptr().align_offset(ALIGNMENT) + offset;
+                let buf = &mut storage[start..start + LEN];
+                buf.copy_from_slice(&expected);
+
+                for len in [0, 1, 15, 16, 17, 63, 64, 65, 1000] {
+                    let probes = &probes[..len];
+                    let mut out = vec![false; len];
+                    let mut bitmask = vec![u64::MAX; len.div_ceil(64) + 1];
+                    unsafe {
+                        filter.contains_batch(buf.as_ptr(), NUM_BUCKETS, probes, &mut out);
+                        filter.contains_batch_bitmask(
+                            buf.as_ptr(),
+                            NUM_BUCKETS,
+                            probes,
+                            &mut bitmask,
+                        );
+                    }
+
+                    for (i, &hash) in probes.iter().enumerate() {
+                        let expected = unsafe {
+                            fallback::FallbackFilter.contains(expected.as_ptr(), NUM_BUCKETS, hash)
+                        };
+                        assert_eq!(out[i], expected, "{} offset {}", filter.backend(), offset);
+                        assert_eq!(
+                            bitmask[i / 64] >> (i % 64) & 1 == 1,
+                            expected,
+                            "{} offset {}",
+                            filter.backend(),
+                            offset
+                        );
+                    }
+                    if len % 64 != 0 {
+                        assert_eq!(bitmask[len / 64] >> (len % 64), 0);
+                    }
+                    assert_eq!(*bitmask.last().unwrap(), u64::MAX);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn insert_batch_matches_single() {
+        const NUM_BUCKETS: usize = 5;
+        const LEN: usize = NUM_BUCKETS * BUCKET_SIZE;
+
+        let probes = hashes(100).chain(hashes(50)).collect::<Vec<_>>();
+
+        let mut expected = vec![0u8; LEN];
+        let mut expected_present = 0;
+        for &hash in probes.iter() {
+            expected_present += unsafe {
+                fallback::FallbackFilter.insert(expected.as_mut_ptr(), NUM_BUCKETS, hash)
+            } as usize;
+        }
+
+        for filter in impls() {
+            for offset in [0, 3, 32] {
+                let mut storage = vec![0u8; LEN + 2 * ALIGNMENT];
+                let start = storage.as_ptr().align_offset(ALIGNMENT) + offset;
+                let buf = &mut storage[start..start + LEN];
+
+                let present =
+                    unsafe { filter.insert_batch(buf.as_mut_ptr(), NUM_BUCKETS, &probes) };
+                assert_eq!(
+                    present,
+                    expected_present,
+                    "{} offset {}",
+                    filter.backend(),
+                    offset
+                );
+                assert_eq!(
+                    buf,
+                    expected.as_slice(),
+                    "{} offset {}",
+                    filter.backend(),
+                    offset
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn huge_num_buckets() {
+        // Hashes with small upper halves map to the first buckets even if there are a lot of them,
+        // so a small buffer is enough to check that backends agree on the bucket index.
+        const LEN: usize = 64 * BUCKET_SIZE;
+
+        let probes = hashes(500).map(|h| h % (60 << 32)).collect::<Vec<_>>();
+
+        for num_buckets in [
+            crate::MAX_NUM_BUCKETS,
+            crate::MAX_NUM_BUCKETS - 12345,
+            (1 << 31) + 7,
+            (1 << 28) + 1,
+        ] {
+            for &hash in probes.iter() {
+                let idx = bucket_index(num_buckets, hash);
+                assert_eq!(idx as u64, ((hash >> 32) * num_buckets as u64) >> 32);
+                assert!(idx < 60);
+            }
+
+            let mut expected = vec![0u8; LEN];
+            for &hash in probes.iter() {
+                unsafe {
+                    fallback::FallbackFilter.insert(expected.as_mut_ptr(), num_buckets, hash);
+                }
+            }
+
+            for filter in impls() {
+                let mut storage = vec![0u8; LEN + ALIGNMENT];
+                let start = storage.as_ptr().align_offset(ALIGNMENT);
+                let buf = &mut storage[start..start + LEN];
+
+                for &hash in probes.iter() {
+                    unsafe { filter.insert(buf.as_mut_ptr(), num_buckets, hash) };
+                }
+                assert_eq!(
+                    buf,
+                    expected.as_slice(),
+                    "{} {}",
+                    filter.backend(),
+                    num_buckets
+                );
+
+                buf.fill(0);
+                unsafe { filter.insert_batch(buf.as_mut_ptr(), num_buckets, &probes) };
+                assert_eq!(
+                    buf,
+                    expected.as_slice(),
+                    "{} {}",
+                    filter.backend(),
+                    num_buckets
+                );
+
+                let mut out = vec![false; probes.len()];
+                unsafe { filter.contains_batch(buf.as_ptr(), num_buckets, &probes, &mut out) };
+                assert!(
+                    out.iter().all(|&x| x),
+                    "{} {}",
+                    filter.backend(),
+                    num_buckets
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn set_ops_match_fallback() {
+        const NUM_BUCKETS: usize = 7;
+        const LEN: usize = NUM_BUCKETS * BUCKET_SIZE;
+
+        let mut a = vec![0u8; LEN];
+        let mut b = vec![0u8; LEN];
+        for (i, hash) in hashes(200).enumerate() {
+            let buf = if i % 3 == 0 { &mut a } else { &mut b };
+            unsafe { fallback::FallbackFilter.insert(buf.as_mut_ptr(), NUM_BUCKETS, hash) };
+        }
+        let mut union = b.clone();
+        let mut intersection = b.clone();
+        unsafe {
+            fallback::FallbackFilter.union(union.as_mut_ptr(), a.as_ptr(), NUM_BUCKETS);
+            fallback::FallbackFilter.intersect(intersection.as_mut_ptr(), a.as_ptr(), NUM_BUCKETS);
+        }
+        assert!(union.iter().zip(&b).all(|(u, b)| u | b == *u));
+        assert!(intersection.iter().zip(&b).all(|(i, b)| i & b == *i));
+
+        for filter in impls() {
+            for offset in [0, 1, 32] {
+                let mut storage = vec![0u8; LEN + 2 * ALIGNMENT];
+                let start = storage.as_ptr().align_offset(ALIGNMENT) + offset;
+                let dst = &mut storage[start..start + LEN];
+
+                dst.copy_from_slice(&b);
+                unsafe { filter.union(dst.as_mut_ptr(), a.as_ptr(), NUM_BUCKETS) };
+                assert_eq!(
+                    dst,
+                    union.as_slice(),
+                    "{} offset {}",
+                    filter.backend(),
+                    offset
+                );
+
+                dst.copy_from_slice(&b);
+                unsafe { filter.intersect(dst.as_mut_ptr(), a.as_ptr(), NUM_BUCKETS) };
+                assert_eq!(
+                    dst,
+                    intersection.as_slice(),
+                    "{} offset {}",
+                    filter.backend(),
+                    offset
+                );
+
+                unsafe {
+                    assert!(filter.is_subset(a.as_ptr(), union.as_ptr(), NUM_BUCKETS));
+                    assert!(filter.is_subset(b.as_ptr(), union.as_ptr(), NUM_BUCKETS));
+                    assert!(filter.is_subset(dst.as_ptr(), a.as_ptr(), NUM_BUCKETS));
+                    assert!(filter.is_subset(dst.as_ptr(), b.as_ptr(), NUM_BUCKETS));
+                    assert!(!filter.is_subset(union.as_ptr(), a.as_ptr(), NUM_BUCKETS));
+                    assert!(!filter.is_subset(union.as_ptr(), b.as_ptr(), NUM_BUCKETS));
+                }
+
+                // a single differing bit in the last bucket
+                let mut c = a.clone();
+                c[LEN - 1] |= !a[LEN - 1] & (!a[LEN - 1]).wrapping_neg();
+                assert_ne!(c, a);
+                unsafe {
+                    assert!(!filter.is_subset(c.as_ptr(), a.as_ptr(), NUM_BUCKETS));
+                    assert!(filter.is_subset(a.as_ptr(), c.as_ptr(), NUM_BUCKETS));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn count_ones_matches_bytes() {
+        const NUM_BUCKETS: usize = 13;
+        const LEN: usize = NUM_BUCKETS * BUCKET_SIZE;
+
+        let mut storage = vec![0u8; LEN + 2 * ALIGNMENT];
+        for (i, byte) in storage.iter_mut().enumerate() {
+            *byte = (i as u8).wrapping_mul(151) ^ 0x5a;
+        }
+        for filter in impls() {
+            for offset in [0, 1, 32] {
+                let start = storage.as_ptr().align_offset(ALIGNMENT) + offset;
+                let buf = &storage[start..start + LEN];
+                let expected = buf.iter().map(|b| u64::from(b.count_ones())).sum::<u64>();
+                assert_eq!(
+                    unsafe { filter.count_ones(buf.as_ptr(), NUM_BUCKETS) },
+                    expected,
+                    "{} offset {}",
+                    filter.backend(),
+                    offset
+                );
+            }
+            let full = vec![0xffu8; LEN];
+            assert_eq!(
+                unsafe { filter.count_ones(full.as_ptr(), NUM_BUCKETS) },
+                LEN as u64 * 8
+            );
+
+            let a = &storage[..LEN];
+            let b = &storage[ALIGNMENT + 1..ALIGNMENT + 1 + LEN];
+            let expected = a
+                .iter()
+                .zip(b)
+                .map(|(a, b)| u64::from((a | b).count_ones()))
+                .sum::<u64>();
+            assert_eq!(
+                unsafe { filter.count_ones_union(a.as_ptr(), b.as_ptr(), NUM_BUCKETS) },
+                expected,
+                "{}",
+                filter.backend()
+            );
+        }
+    }
+
+    #[test]
+    fn contains_any_matches_single() {
+        const NUM_BUCKETS: usize = 5;
+        const NUM_FILTERS: usize = 3;
+        const LEN: usize = NUM_FILTERS * NUM_BUCKETS * BUCKET_SIZE;
+
+        let mut filters = vec![0u8; LEN];
+        for (i, hash) in hashes(60).enumerate() {
+            let filter = &mut filters[(i % NUM_FILTERS) * NUM_BUCKETS * BUCKET_SIZE..];
+            unsafe { fallback::FallbackFilter.insert(filter.as_mut_ptr(), NUM_BUCKETS, hash) };
+        }
+
+        for filter in impls() {
+            for offset in [0, 1, 32] {
+                let mut storage = vec![0u8; LEN + 2 * ALIGNMENT];
+                let start = storage.as_ptr().align_offset(ALIGNMENT) + offset;
+                let buf = &mut storage[start..start + LEN];
+                buf.copy_from_slice(&filters);
+
+                for hash in hashes(1000) {
+                    for num_filters in 0..=NUM_FILTERS {
+                        let expected = (0..num_filters).any(|i| unsafe {
+                            fallback::FallbackFilter.contains(
+                                filters[i * NUM_BUCKETS * BUCKET_SIZE..].as_ptr(),
+                                NUM_BUCKETS,
+                                hash,
+                            )
+                        });
+                        let res = unsafe {
+                            filter.contains_any(buf.as_ptr(), NUM_BUCKETS, num_filters, hash)
+                        };
+                        assert_eq!(res, expected, "{} offset {}", filter.backend(), offset);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn all_backends_match_parquet2() {
+        // goes through load_backend instead of impls() so it covers exactly what FilterFn can pick
+        let backends = Backend::ALL
+            .into_iter()
+            .filter_map(load_backend)
+            .collect::<Vec<_>>();
+        assert!(backends
+            .iter()
+            .any(|filter| filter.backend() == load().backend()));
+
+        for filter in backends {
+            assert!(self_test(filter), "{}", filter.backend());
+
+            for num_buckets in [1, 7, 64, 1000] {
+                let len = num_buckets * BUCKET_SIZE;
+                let mut expected = vec![0u8; len];
+                let mut storage = vec![0u8; len + ALIGNMENT];
+                let start = storage.as_ptr().align_offset(ALIGNMENT);
+                let buf = &mut storage[start..start + len];
+
+                for hash in hashes(num_buckets as u64 * 4) {
+                    let was_present = parquet2::bloom_filter::is_in_set(&expected, hash);
+                    parquet2::bloom_filter::insert(&mut expected, hash);
+                    let res = unsafe { filter.insert(buf.as_mut_ptr(), num_buckets, hash) };
+                    assert_eq!(res, was_present, "{}", filter.backend());
+                }
+                assert_eq!(buf, expected.as_slice(), "{}", filter.backend());
+
+                let mut batch = vec![0u8; len];
+                unsafe {
+                    let hashes = hashes(num_buckets as u64 * 4).collect::<Vec<_>>();
+                    filter.insert_batch(batch.as_mut_ptr(), num_buckets, &hashes);
+                }
+                assert_eq!(batch, expected, "{}", filter.backend());
+
+                for hash in hashes(num_buckets as u64 * 16) {
+                    let res = unsafe { filter.contains(buf.as_ptr(), num_buckets, hash) };
+                    assert_eq!(
+                        res,
+                        parquet2::bloom_filter::is_in_set(&expected, hash),
+                        "{}",
+                        filter.backend()
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,395 @@
+use super::SALT;
+use core::arch::asm;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use crate::{Backend, FilterImpl, BUCKET_SIZE};
+
+// Vector intrinsics and the `v` target feature aren't stable, so the kernels are written in
+// inline asm and `.option arch, +v` lets the assembler accept vector instructions without
+// enabling the feature for the whole crate.
+//
+// V guarantees VLEN >= 128, so a bucket fits in a register group at LMUL=2 both as 8 u32 and as
+// 32 bytes. Buckets are always loaded as bytes so they don't need to be aligned.
+
+pub struct RvvFilter;
+
+/// Returns true if the kernel reports the V extension, which also means it saves vector
+/// registers on context switch. Cached after the first call.
+pub(super) fn has_v() -> bool {
+    const UNINIT: u8 = u8::MAX;
+    const HWCAP_V: libc::c_ulong = 1 << (b'V' - b'A');
+    static HAS_V: AtomicU8 = AtomicU8::new(UNINIT);
+
+    match HAS_V.load(Ordering::Relaxed) {
+        UNINIT => {
+            let res = unsafe { libc::getauxval(libc::AT_HWCAP) } & HWCAP_V != 0;
+            HAS_V.store(res as u8, Ordering::Relaxed);
+            res
+        }
+        val => val == 1,
+    }
+}
+
+impl RvvFilter {
+    /// Computes `mask[i] = 1 << ((hash * SALT[i]) >> 27)`.
+    #[inline]
+    unsafe fn make_mask(hash: u32) -> [u32; 8] {
+        let mut mask = [0u32; 8];
+        asm!(
+            ".option push",
+            ".option arch, +v",
+            "vsetivli zero, 8, e32, m2, ta, ma",
+            "vle32.v v8, ({salt})",
+            "vmul.vx v8, v8, {hash}",
+            "vsrl.vi v8, v8, 27",
+            "vmv.v.i v10, 1",
+            "vsll.vv v10, v10, v8",
+            "vse32.v v10, ({mask})",
+            ".option pop",
+            salt = in(reg) SALT.as_ptr(),
+            hash = in(reg) hash,
+            mask = in(reg) mask.as_mut_ptr(),
+            out("v8") _,
+            out("v9") _,
+            out("v10") _,
+            out("v11") _,
+            options(nostack),
+        );
+        mask
+    }
+
+    #[inline]
+    unsafe fn check_mask(bucket: *const u8, mask: &[u32; 8]) -> bool {
+        let num_missing: usize;
+        asm!(
+            ".option push",
+            ".option arch, +v",
+            "vsetvli zero, {bucket_size}, e8, m2, ta, ma",
+            "vle8.v v12, ({bucket})",
+            "vsetivli zero, 8, e32, m2, ta, ma",
+            "vle32.v v10, ({mask})",
+            "vand.vv v12, v12, v10",
+            "vmsne.vv v14, v12, v10",
+            "vcpop.m {num_missing}, v14",
+            ".option pop",
+            bucket_size = in(reg) BUCKET_SIZE,
+            bucket = in(reg) bucket,
+            mask = in(reg) mask.as_ptr(),
+            num_missing = lateout(reg) num_missing,
+            out("v10") _,
+            out("v11") _,
+            out("v12") _,
+            out("v13") _,
+            out("v14") _,
+            options(readonly, nostack),
+        );
+        num_missing == 0
+    }
+
+    #[inline]
+    unsafe fn insert_mask(bucket: *mut u8, mask: &[u32; 8]) -> bool {
+        let num_missing: usize;
+        asm!(
+            ".option push",
+            ".option arch, +v",
+            "vsetvli zero, {bucket_size}, e8, m2, ta, ma",
+            "vle8.v v12, ({bucket})",
+            "vsetivli zero, 8, e32, m2, ta, ma",
+            "vle32.v v10, ({mask})",
+            "vand.vv v14, v12, v10",
+            "vmsne.vv v16, v14, v10",
+            "vcpop.m {num_missing}, v16",
+            "vor.vv v12, v12, v10",
+            "vsetvli zero, {bucket_size}, e8, m2, ta, ma",
+            "vse8.v v12, ({bucket})",
+            ".option pop",
+            bucket_size = in(reg) BUCKET_SIZE,
+            bucket = in(reg) bucket,
+            mask = in(reg) mask.as_ptr(),
+            num_missing = lateout(reg) num_missing,
+            out("v10") _,
+            out("v11") _,
+            out("v12") _,
+            out("v13") _,
+            out("v14") _,
+            out("v15") _,
+            out("v16") _,
+            options(nostack),
+        );
+        num_missing == 0
+    }
+
+    #[inline]
+    unsafe fn check_bucket(bucket: *const u8, hash: u32) -> bool {
+        Self::check_mask(bucket, &Self::make_mask(hash))
+    }
+
+    #[inline]
+    unsafe fn insert_bucket(bucket: *mut u8, hash: u32) -> bool {
+        Self::insert_mask(bucket, &Self::make_mask(hash))
+    }
+}
+
+impl FilterImpl for RvvFilter {
+    #[inline]
+    unsafe fn contains(&self, buf: *const u8, num_buckets: usize, hash: u64) -> bool {
+        self.contains_unaligned(buf, num_buckets, hash)
+    }
+    #[inline]
+    unsafe fn insert(&self, buf: *mut u8, num_buckets: usize, hash: u64) -> bool {
+        self.insert_unaligned(buf, num_buckets, hash)
+    }
+    #[inline]
+    unsafe fn contains_unaligned(&self, buf: *const u8, num_buckets: usize, hash: u64) -> bool {
+        let bucket_idx = super::bucket_index(num_buckets, hash);
+        Self::check_bucket(buf.add(bucket_idx * BUCKET_SIZE), hash as u32)
+    }
+    #[inline]
+    unsafe fn insert_unaligned(&self, buf: *mut u8, num_buckets: usize, hash: u64) -> bool {
+        let bucket_idx = super::bucket_index(num_buckets, hash);
+        Self::insert_bucket(buf.add(bucket_idx * BUCKET_SIZE), hash as u32)
+    }
+    unsafe fn insert_batch(&self, buf: *mut u8, num_buckets: usize, hashes: &[u64]) -> usize {
+        super::insert_batch(buf, num_buckets, hashes, |offsets, hashes| {
+            super::insert_each(buf, offsets, hashes, |bucket, hash| {
+                Self::insert_bucket(bucket, hash)
+            })
+        })
+    }
+    unsafe fn contains_batch(
+        &self,
+        buf: *const u8,
+        num_buckets: usize,
+        hashes: &[u64],
+        out: &mut [bool],
+    ) {
+        super::contains_batch(buf, num_buckets, hashes, out, |offsets, hashes| {
+            super::check_each(buf, offsets, hashes, |bucket, hash| {
+                Self::check_bucket(bucket, hash)
+            })
+        })
+    }
+    unsafe fn contains_batch_bitmask(
+        &self,
+        buf: *const u8,
+        num_buckets: usize,
+        hashes: &[u64],
+        out: &mut [u64],
+    ) {
+        super::contains_batch_bitmask(buf, num_buckets, hashes, out, |offsets, hashes| {
+            super::check_each(buf, offsets, hashes, |bucket, hash| {
+                Self::check_bucket(bucket, hash)
+            })
+        })
+    }
+    unsafe fn union(&self, dst: *mut u8, src: *const u8, num_buckets: usize) {
+        asm!(
+            ".option push",
+            ".option arch, +v",
+            "2:",
+            "beqz {len}, 3f",
+            "vsetvli {vl}, {len}, e8, m8, ta, ma",
+            "vle8.v v8, ({dst})",
+            "vle8.v v16, ({src})",
+            "vor.vv v8, v8, v16",
+            "vse8.v v8, ({dst})",
+            "add {dst}, {dst}, {vl}",
+            "add {src}, {src}, {vl}",
+            "sub {len}, {len}, {vl}",
+            "j 2b",
+            "3:",
+            ".option pop",
+            dst = inout(reg) dst => _,
+            src = inout(reg) src => _,
+            len = inout(reg) num_buckets * BUCKET_SIZE => _,
+            vl = out(reg) _,
+            out("v8") _,
+            out("v9") _,
+            out("v10") _,
+            out("v11") _,
+            out("v12") _,
+            out("v13") _,
+            out("v14") _,
+            out("v15") _,
+            out("v16") _,
+            out("v17") _,
+            out("v18") _,
+            out("v19") _,
+            out("v20") _,
+            out("v21") _,
+            out("v22") _,
+            out("v23") _,
+            options(nostack),
+        );
+    }
+    unsafe fn intersect(&self, dst: *mut u8, src: *const u8, num_buckets: usize) {
+        asm!(
+            ".option push",
+            ".option arch, +v",
+            "2:",
+            "beqz {len}, 3f",
+            "vsetvli {vl}, {len}, e8, m8, ta, ma",
+            "vle8.v v8, ({dst})",
+            "vle8.v v16, ({src})",
+            "vand.vv v8, v8, v16",
+            "vse8.v v8, ({dst})",
+            "add {dst}, {dst}, {vl}",
+            "add {src}, {src}, {vl}",
+            "sub {len}, {len}, {vl}",
+            "j 2b",
+            "3:",
+            ".option pop",
+            dst = inout(reg) dst => _,
+            src = inout(reg) src => _,
+            len = inout(reg) num_buckets * BUCKET_SIZE => _,
+            vl = out(reg) _,
+            out("v8") _,
+            out("v9") _,
+            out("v10") _,
+            out("v11") _,
+            out("v12") _,
+            out("v13") _,
+            out("v14") _,
+            out("v15") _,
+            out("v16") _,
+            out("v17") _,
+            out("v18") _,
+            out("v19") _,
+            out("v20") _,
+            out("v21") _,
+            out("v22") _,
+            out("v23") _,
+            options(nostack),
+        );
+    }
+    unsafe fn is_subset(&self, a: *const u8, b: *const u8, num_buckets: usize) -> bool {
+        let num_extra: usize;
+        asm!(
+            ".option push",
+            ".option arch, +v",
+            "li {num_extra}, 0",
+            "2:",
+            "beqz {len}, 3f",
+            "vsetvli {vl}, {len}, e8, m8, ta, ma",
+            "vle8.v v8, ({a})",
+            "vle8.v v16, ({b})",
+            // bytes of `a` that have bits which aren't in `b`
+            "vnot.v v16, v16",
+            "vand.vv v8, v8, v16",
+            "vmsne.vi v0, v8, 0",
+            "vcpop.m {num_extra}, v0",
+            "bnez {num_extra}, 3f",
+            "add {a}, {a}, {vl}",
+            "add {b}, {b}, {vl}",
+            "sub {len}, {len}, {vl}",
+            "j 2b",
+            "3:",
+            ".option pop",
+            a = inout(reg) a => _,
+            b = inout(reg) b => _,
+            len = inout(reg) num_buckets * BUCKET_SIZE => _,
+            vl = out(reg) _,
+            num_extra = out(reg) num_extra,
+            out("v0") _,
+            out("v8") _,
+            out("v9") _,
+            out("v10") _,
+            out("v11") _,
+            out("v12") _,
+            out("v13") _,
+            out("v14") _,
+            out("v15") _,
+            out("v16") _,
+            out("v17") _,
+            out("v18") _,
+            out("v19") _,
+            out("v20") _,
+            out("v21") _,
+            out("v22") _,
+            out("v23") _,
+            options(readonly, nostack),
+        );
+        num_extra == 0
+    }
+    unsafe fn contains_any(
+        &self,
+        buf: *const u8,
+        num_buckets: usize,
+        num_filters: usize,
+        hash: u64,
+    ) -> bool {
+        let mask = Self::make_mask(hash as u32);
+        super::contains_any(buf, num_buckets, num_filters, hash, |bucket| {
+            Self::check_mask(bucket, &mask)
+        })
+    }
+    unsafe fn count_ones(&self, buf: *const u8, num_buckets: usize) -> u64 {
+        let num_ones: u64;
+        // There is no vector popcount in V, but vcpop.m counts the bits of a mask register.
+        // At most VLEN/8 bytes are loaded at a time so all of their bits fit in one register.
+        asm!(
+            ".option push",
+            ".option arch, +v",
+            "li {num_ones}, 0",
+            "2:",
+            "beqz {len}, 3f",
+            "vsetvli {vl}, {len}, e8, m1, ta, ma",
+            "vle8.v v8, ({buf})",
+            "slli {tmp}, {vl}, 3",
+            "vsetvli zero, {tmp}, e8, m8, ta, ma",
+            "vcpop.m {tmp}, v8",
+            "add {num_ones}, {num_ones}, {tmp}",
+            "add {buf}, {buf}, {vl}",
+            "sub {len}, {len}, {vl}",
+            "j 2b",
+            "3:",
+            ".option pop",
+            buf = inout(reg) buf => _,
+            len = inout(reg) num_buckets * BUCKET_SIZE => _,
+            vl = out(reg) _,
+            tmp = out(reg) _,
+            num_ones = out(reg) num_ones,
+            out("v8") _,
+            options(readonly, nostack),
+        );
+        num_ones
+    }
+    unsafe fn count_ones_union(&self, a: *const u8, b: *const u8, num_buckets: usize) -> u64 {
+        let num_ones: u64;
+        // same as count_ones, with the bytes of both filters or'ed together first
+        asm!(
+            ".option push",
+            ".option arch, +v",
+            "li {num_ones}, 0",
+            "2:",
+            "beqz {len}, 3f",
+            "vsetvli {vl}, {len}, e8, m1, ta, ma",
+            "vle8.v v8, ({a})",
+            "vle8.v v9, ({b})",
+            "vor.vv v8, v8, v9",
+            "slli {tmp}, {vl}, 3",
+            "vsetvli zero, {tmp}, e8, m8, ta, ma",
+            "vcpop.m {tmp}, v8",
+            "add {num_ones}, {num_ones}, {tmp}",
+            "add {a}, {a}, {vl}",
+            "add {b}, {b}, {vl}",
+            "sub {len}, {len}, {vl}",
+            "j 2b",
+            "3:",
+            ".option pop",
+            a = inout(reg) a => _,
+            b = inout(reg) b => _,
+            len = inout(reg) num_buckets * BUCKET_SIZE => _,
+            vl = out(reg) _,
+            tmp = out(reg) _,
+            num_ones = out(reg) num_ones,
+            out("v8") _,
+            out("v9") _,
+            options(readonly, nostack),
+        );
+        num_ones
+    }
+    fn backend(&self) -> Backend {
+        Backend::Rvv
+    }
+}
@@ -1,10 +1,11 @@
 use super::SALT;
 use core::arch::wasm::{
-    u32x4, u32x4_extract_lane, u32x4_mul, u32x4_shr, u32x4_splat, v128, v128_andnot, v128_any_true,
-    v128_or, v128_store,
+    i8x16_popcnt, u16x8_extadd_pairwise_u8x16, u32x4, u32x4_extadd_pairwise_u16x8,
+    u32x4_extract_lane, u32x4_mul, u32x4_shr, u32x4_splat, u8x16_add, v128, v128_and, v128_andnot,
+    v128_any_true, v128_load, v128_or, v128_store,
 };
 
-use crate::FilterImpl;
+use crate::{Backend, FilterImpl};
 
 pub struct WasmFilter;
 
@@ -41,15 +42,55 @@ impl WasmFilter {
     unsafe fn check(mask: v128, bucket: v128) -> bool {
         !v128_any_true(v128_andnot(mask, bucket))
     }
+
+    #[inline(always)]
+    unsafe fn check_unaligned(bucket: *const u8, hash: u32) -> bool {
+        let mask = Self::make_mask(hash);
+        // v128_load doesn't require the address to be aligned
+        let bucket = bucket as *const v128;
+
+        let bucket = (v128_load(bucket), v128_load(bucket.add(1)));
+
+        Self::check(mask.0, bucket.0) && Self::check(mask.1, bucket.1)
+    }
+
+    #[inline(always)]
+    unsafe fn insert_unaligned_bucket(bucket: *mut u8, hash: u32) -> bool {
+        let mask = Self::make_mask(hash);
+        // v128_store doesn't require the address to be aligned either
+        let bucket = bucket as *mut v128;
+
+        let val = (v128_load(bucket), v128_load(bucket.add(1)));
+
+        let res = Self::check(mask.0, val.0) && Self::check(mask.1, val.1);
+
+        v128_store(bucket, v128_or(val.0, mask.0));
+        v128_store(bucket.add(1), v128_or(val.1, mask.1));
+
+        res
+    }
+
+    /// Number of set bits in a bucket, given as its two halves.
+    #[inline(always)]
+    unsafe fn bucket_ones(lo: v128, hi: v128) -> u64 {
+        // at most 16 bits per byte lane, so adding the two halves can't overflow
+        let ones = u8x16_add(i8x16_popcnt(lo), i8x16_popcnt(hi));
+        let ones = u32x4_extadd_pairwise_u16x8(u16x8_extadd_pairwise_u8x16(ones));
+        u64::from(
+            u32x4_extract_lane::<0>(ones)
+                + u32x4_extract_lane::<1>(ones)
+                + u32x4_extract_lane::<2>(ones)
+                + u32x4_extract_lane::<3>(ones),
+        )
+    }
 }
 
 impl FilterImpl for WasmFilter {
     #[inline(always)]
     unsafe fn contains(&self, buf: *const u8, num_buckets: usize, hash: u64) -> bool {
-        let bucket_idx =
-            fastrange_rs::fastrange_32(hash.rotate_left(32) as u32, num_buckets as u32);
+        let bucket_idx = super::bucket_index(num_buckets, hash);
         let mask = Self::make_mask(hash as u32);
-        let bucket = (buf as *const u32).add((bucket_idx * 8) as usize);
+        let bucket = (buf as *const u32).add(bucket_idx * 8);
 
         let bucket = (Self::load_v(bucket), Self::load_v(bucket.add(4)));
 
@@ -57,10 +98,9 @@ impl FilterImpl for WasmFilter {
     }
     #[inline(always)]
     unsafe fn insert(&self, buf: *mut u8, num_buckets: usize, hash: u64) -> bool {
-        let bucket_idx =
-            fastrange_rs::fastrange_32(hash.rotate_left(32) as u32, num_buckets as u32);
+        let bucket_idx = super::bucket_index(num_buckets, hash);
         let mask = Self::make_mask(hash as u32);
-        let bucket = (buf as *mut u32).add((bucket_idx * 8) as usize);
+        let bucket = (buf as *mut u32).add(bucket_idx * 8);
 
         let val = (Self::load_v(bucket), Self::load_v(bucket.add(4)));
 
@@ -74,48 +114,123 @@ impl FilterImpl for WasmFilter {
 
         res
     }
-    fn which(&self) -> &'static str {
-        "WasmFilter"
+    #[inline(always)]
+    unsafe fn contains_unaligned(&self, buf: *const u8, num_buckets: usize, hash: u64) -> bool {
+        let bucket_idx = super::bucket_index(num_buckets, hash);
+        Self::check_unaligned(buf.add(bucket_idx * 32), hash as u32)
     }
-}
-
-#[cfg(test)]
-mod test {
-    use super::*;
-    use crate::ALIGNMENT;
-    use std::alloc::{alloc_zeroed, dealloc, Layout};
-
-    struct Buf {
-        ptr: *mut u8,
-        layout: Layout,
+    #[inline(always)]
+    unsafe fn insert_unaligned(&self, buf: *mut u8, num_buckets: usize, hash: u64) -> bool {
+        let bucket_idx = super::bucket_index(num_buckets, hash);
+        Self::insert_unaligned_bucket(buf.add(bucket_idx * 32), hash as u32)
     }
-
-    impl Buf {
-        fn new(len: usize) -> Self {
-            let layout = Layout::from_size_align(len, ALIGNMENT).unwrap();
-            let ptr = unsafe { alloc_zeroed(layout) };
-
-            Self { layout, ptr }
+    unsafe fn insert_batch(&self, buf: *mut u8, num_buckets: usize, hashes: &[u64]) -> usize {
+        super::insert_batch(buf, num_buckets, hashes, |offsets, hashes| {
+            super::insert_each(buf, offsets, hashes, |bucket, hash| {
+                Self::insert_unaligned_bucket(bucket, hash)
+            })
+        })
+    }
+    unsafe fn contains_batch(
+        &self,
+        buf: *const u8,
+        num_buckets: usize,
+        hashes: &[u64],
+        out: &mut [bool],
+    ) {
+        super::contains_batch(buf, num_buckets, hashes, out, |offsets, hashes| {
+            super::check_each(buf, offsets, hashes, |bucket, hash| {
+                Self::check_unaligned(bucket, hash)
+            })
+        })
+    }
+    unsafe fn contains_batch_bitmask(
+        &self,
+        buf: *const u8,
+        num_buckets: usize,
+        hashes: &[u64],
+        out: &mut [u64],
+    ) {
+        super::contains_batch_bitmask(buf, num_buckets, hashes, out, |offsets, hashes| {
+            super::check_each(buf, offsets, hashes, |bucket, hash| {
+                Self::check_unaligned(bucket, hash)
+            })
+        })
+    }
+    unsafe fn union(&self, dst: *mut u8, src: *const u8, num_buckets: usize) {
+        for i in 0..num_buckets * 2 {
+            let dst = (dst as *mut v128).add(i);
+            let src = (src as *const v128).add(i);
+            v128_store(dst, v128_or(v128_load(dst), v128_load(src)));
         }
     }
-
-    impl Drop for Buf {
-        fn drop(&mut self) {
-            unsafe {
-                dealloc(self.ptr, self.layout);
-            }
+    unsafe fn intersect(&self, dst: *mut u8, src: *const u8, num_buckets: usize) {
+        for i in 0..num_buckets * 2 {
+            let dst = (dst as *mut v128).add(i);
+            let src = (src as *const v128).add(i);
+            v128_store(dst, v128_and(v128_load(dst), v128_load(src)));
         }
     }
+    unsafe fn is_subset(&self, a: *const u8, b: *const u8, num_buckets: usize) -> bool {
+        (0..num_buckets * 2).all(|i| {
+            let a = v128_load((a as *const v128).add(i));
+            let b = v128_load((b as *const v128).add(i));
+            // bits of `a` that aren't in `b`
+            !v128_any_true(v128_andnot(a, b))
+        })
+    }
+    unsafe fn contains_any(
+        &self,
+        buf: *const u8,
+        num_buckets: usize,
+        num_filters: usize,
+        hash: u64,
+    ) -> bool {
+        let mask = Self::make_mask(hash as u32);
+        super::contains_any(buf, num_buckets, num_filters, hash, |bucket| {
+            let bucket = bucket as *const v128;
+            Self::check(mask.0, v128_load(bucket)) && Self::check(mask.1, v128_load(bucket.add(1)))
+        })
+    }
+    unsafe fn count_ones(&self, buf: *const u8, num_buckets: usize) -> u64 {
+        (0..num_buckets)
+            .map(|i| {
+                let bucket = (buf as *const v128).add(i * 2);
+                Self::bucket_ones(v128_load(bucket), v128_load(bucket.add(1)))
+            })
+            .sum()
+    }
+    unsafe fn count_ones_union(&self, a: *const u8, b: *const u8, num_buckets: usize) -> u64 {
+        (0..num_buckets)
+            .map(|i| {
+                let a = (a as *const v128).add(i * 2);
+                let b = (b as *const v128).add(i * 2);
+                Self::bucket_ones(
+                    v128_or(v128_load(a), v128_load(b)),
+                    v128_or(v128_load(a.add(1)), v128_load(b.add(1))),
+                )
+            })
+            .sum()
+    }
+    fn backend(&self) -> Backend {
+        Backend::Wasm
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod test {
+    use super::*;
+    use crate::Sbbf;
 
     #[test]
     fn smoke_test_wasm() {
+        let mut filter = Sbbf::new(2);
+        let buf = filter.as_bytes_mut().as_mut_ptr();
         unsafe {
-            let buf = Buf::new(64);
-
-            assert!(!WasmFilter.insert(buf.ptr, 2, 69));
-            assert!(WasmFilter.contains(buf.ptr, 2, 69));
-            assert!(!WasmFilter.contains(buf.ptr, 2, 12));
-            assert!(WasmFilter.insert(buf.ptr, 2, 69));
+            assert!(!WasmFilter.insert(buf, 2, 69));
+            assert!(WasmFilter.contains(buf, 2, 69));
+            assert!(!WasmFilter.contains(buf, 2, 12));
+            assert!(WasmFilter.insert(buf, 2, 69));
         }
     }
 }
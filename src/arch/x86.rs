@@ -1,25 +1,341 @@
 #[cfg(target_arch = "x86")]
 use core::arch::x86::{
-    __m128i, __m256i, _mm256_load_si256, _mm256_mullo_epi32, _mm256_or_si256, _mm256_set1_epi32,
-    _mm256_setr_epi32, _mm256_sllv_epi32, _mm256_srli_epi32, _mm256_store_si256,
-    _mm256_testc_si256, _mm_add_epi32, _mm_castsi128_ps, _mm_cvtps_epi32, _mm_mullo_epi32,
-    _mm_or_si128, _mm_set1_epi32, _mm_setr_epi32, _mm_slli_epi32, _mm_srli_epi32, _mm_storeu_si128,
-    _mm_testc_si128,
+    __m128i, __m256i, __m512i, _mm256_add_epi32, _mm256_add_epi64, _mm256_add_epi8,
+    _mm256_and_si256, _mm256_blend_epi32, _mm256_castsi256_ps, _mm256_cmpeq_epi32,
+    _mm256_i32gather_epi32, _mm256_load_si256, _mm256_loadu_si256, _mm256_movemask_ps,
+    _mm256_mul_epu32, _mm256_mullo_epi32, _mm256_or_si256, _mm256_permutevar8x32_epi32,
+    _mm256_sad_epu8, _mm256_set1_epi32, _mm256_set1_epi64x, _mm256_set1_epi8, _mm256_setr_epi32,
+    _mm256_setr_epi8, _mm256_setzero_si256, _mm256_shuffle_epi8, _mm256_slli_epi32,
+    _mm256_slli_epi64, _mm256_sllv_epi32, _mm256_srli_epi16, _mm256_srli_epi32, _mm256_srli_epi64,
+    _mm256_store_si256, _mm256_storeu_si256, _mm256_test_epi32_mask, _mm256_testc_si256,
+    _mm512_and_si512, _mm512_andnot_si512, _mm512_broadcast_i64x4, _mm512_castsi256_si512,
+    _mm512_castsi512_si256, _mm512_extracti64x4_epi64, _mm512_inserti64x4, _mm512_loadu_si512,
+    _mm512_mullo_epi32, _mm512_or_si512, _mm512_set1_epi32, _mm512_sllv_epi32, _mm512_srli_epi32,
+    _mm512_storeu_si512, _mm512_test_epi32_mask, _mm512_test_epi64_mask, _mm_add_epi32,
+    _mm_add_epi64, _mm_add_epi8, _mm_and_si128, _mm_castsi128_ps, _mm_cvtps_epi32, _mm_loadu_si128,
+    _mm_mullo_epi32, _mm_or_si128, _mm_sad_epu8, _mm_set1_epi32, _mm_set1_epi8, _mm_setr_epi32,
+    _mm_setr_epi8, _mm_setzero_si128, _mm_shuffle_epi8, _mm_slli_epi32, _mm_srli_epi16,
+    _mm_srli_epi32, _mm_storeu_si128, _mm_testc_si128,
 };
 #[cfg(target_arch = "x86_64")]
 use core::arch::x86_64::{
-    __m128i, __m256i, _mm256_load_si256, _mm256_mullo_epi32, _mm256_or_si256, _mm256_set1_epi32,
-    _mm256_setr_epi32, _mm256_sllv_epi32, _mm256_srli_epi32, _mm256_store_si256,
-    _mm256_testc_si256, _mm_add_epi32, _mm_castsi128_ps, _mm_cvtps_epi32, _mm_mullo_epi32,
-    _mm_or_si128, _mm_set1_epi32, _mm_setr_epi32, _mm_slli_epi32, _mm_srli_epi32, _mm_storeu_si128,
-    _mm_testc_si128,
+    __m128i, __m256i, __m512i, _mm256_add_epi32, _mm256_add_epi64, _mm256_add_epi8,
+    _mm256_and_si256, _mm256_blend_epi32, _mm256_castsi256_ps, _mm256_cmpeq_epi32,
+    _mm256_i32gather_epi32, _mm256_load_si256, _mm256_loadu_si256, _mm256_movemask_ps,
+    _mm256_mul_epu32, _mm256_mullo_epi32, _mm256_or_si256, _mm256_permutevar8x32_epi32,
+    _mm256_sad_epu8, _mm256_set1_epi32, _mm256_set1_epi64x, _mm256_set1_epi8, _mm256_setr_epi32,
+    _mm256_setr_epi8, _mm256_setzero_si256, _mm256_shuffle_epi8, _mm256_slli_epi32,
+    _mm256_slli_epi64, _mm256_sllv_epi32, _mm256_srli_epi16, _mm256_srli_epi32, _mm256_srli_epi64,
+    _mm256_store_si256, _mm256_storeu_si256, _mm256_test_epi32_mask, _mm256_testc_si256,
+    _mm512_and_si512, _mm512_andnot_si512, _mm512_broadcast_i64x4, _mm512_castsi256_si512,
+    _mm512_castsi512_si256, _mm512_extracti64x4_epi64, _mm512_inserti64x4, _mm512_loadu_si512,
+    _mm512_mullo_epi32, _mm512_or_si512, _mm512_set1_epi32, _mm512_sllv_epi32, _mm512_srli_epi32,
+    _mm512_storeu_si512, _mm512_test_epi32_mask, _mm512_test_epi64_mask, _mm_add_epi32,
+    _mm_add_epi64, _mm_add_epi8, _mm_and_si128, _mm_castsi128_ps, _mm_cvtps_epi32, _mm_loadu_si128,
+    _mm_mullo_epi32, _mm_or_si128, _mm_sad_epu8, _mm_set1_epi32, _mm_set1_epi8, _mm_setr_epi32,
+    _mm_setr_epi8, _mm_setzero_si128, _mm_shuffle_epi8, _mm_slli_epi32, _mm_srli_epi16,
+    _mm_srli_epi32, _mm_storeu_si128, _mm_testc_si128,
 };
 
 use super::SALT;
-use crate::FilterImpl;
+use crate::{Backend, FilterImpl};
+
+pub struct Avx512Filter;
+
+impl Avx512Filter {
+    #[target_feature(enable = "avx512f,avx512vl")]
+    #[inline]
+    unsafe fn salt() -> __m256i {
+        _mm256_setr_epi32(
+            SALT[0] as i32,
+            SALT[1] as i32,
+            SALT[2] as i32,
+            SALT[3] as i32,
+            SALT[4] as i32,
+            SALT[5] as i32,
+            SALT[6] as i32,
+            SALT[7] as i32,
+        )
+    }
+
+    #[target_feature(enable = "avx512f,avx512vl")]
+    #[inline]
+    unsafe fn make_mask(hash: u32) -> __m256i {
+        let mut acc = _mm256_set1_epi32(hash as i32);
+        acc = _mm256_mullo_epi32(Self::salt(), acc);
+        acc = _mm256_srli_epi32(acc, 27);
+        _mm256_sllv_epi32(_mm256_set1_epi32(1), acc)
+    }
+
+    /// Makes the masks of two hashes at once, mask of `a` is in the lower half.
+    #[target_feature(enable = "avx512f,avx512vl")]
+    #[inline]
+    unsafe fn make_mask_pair(a: u32, b: u32) -> __m512i {
+        let salt = _mm512_broadcast_i64x4(Self::salt());
+        let mut acc =
+            _mm512_inserti64x4(_mm512_set1_epi32(a as i32), _mm256_set1_epi32(b as i32), 1);
+        acc = _mm512_mullo_epi32(salt, acc);
+        acc = _mm512_srli_epi32(acc, 27);
+        _mm512_sllv_epi32(_mm512_set1_epi32(1), acc)
+    }
+
+    /// Loads two buckets into one register, `a` is in the lower half.
+    #[target_feature(enable = "avx512f,avx512vl")]
+    #[inline]
+    unsafe fn load_pair(a: *const u8, b: *const u8) -> __m512i {
+        _mm512_inserti64x4(
+            _mm512_castsi256_si512(_mm256_loadu_si256(a as *const __m256i)),
+            _mm256_loadu_si256(b as *const __m256i),
+            1,
+        )
+    }
+
+    /// Returns true if every lane of the bucket has the bit of the matching mask lane set.
+    /// Each mask lane has exactly one bit set so testing for a non-zero AND is enough.
+    #[target_feature(enable = "avx512f,avx512vl")]
+    #[inline]
+    unsafe fn check(val: __m256i, mask: __m256i) -> bool {
+        _mm256_test_epi32_mask(val, mask) == 0xff
+    }
+
+    /// Same as [Avx512Filter::check] for two buckets, the result for the lower half is in bit 0.
+    #[target_feature(enable = "avx512f,avx512vl")]
+    #[inline]
+    unsafe fn check_pair(val: __m512i, mask: __m512i) -> u16 {
+        let k = _mm512_test_epi32_mask(val, mask);
+        ((k & 0xff) == 0xff) as u16 | ((((k >> 8) == 0xff) as u16) << 1)
+    }
+
+    #[target_feature(enable = "avx512f,avx512vl")]
+    #[inline]
+    unsafe fn check_unaligned(bucket: *const u8, hash: u32) -> bool {
+        Self::check(
+            _mm256_loadu_si256(bucket as *const __m256i),
+            Self::make_mask(hash),
+        )
+    }
+
+    #[target_feature(enable = "avx512f,avx512vl")]
+    #[inline]
+    unsafe fn insert_unaligned_bucket(bucket: *mut u8, hash: u32) -> bool {
+        let mask = Self::make_mask(hash);
+        let bucket = bucket as *mut __m256i;
+        let val = _mm256_loadu_si256(bucket);
+        let res = Self::check(val, mask);
+        _mm256_storeu_si256(bucket, _mm256_or_si256(val, mask));
+        res
+    }
+
+    /// Checks the hashes of a chunk two at a time.
+    #[target_feature(enable = "avx512f,avx512vl")]
+    #[inline]
+    unsafe fn check_chunk(buf: *const u8, offsets: &[usize], hashes: &[u64]) -> u16 {
+        let mut res = 0;
+        let mut i = 0;
+        while i + 1 < hashes.len() {
+            let mask = Self::make_mask_pair(
+                *hashes.get_unchecked(i) as u32,
+                *hashes.get_unchecked(i + 1) as u32,
+            );
+            let val = Self::load_pair(
+                buf.add(*offsets.get_unchecked(i)),
+                buf.add(*offsets.get_unchecked(i + 1)),
+            );
+            res |= Self::check_pair(val, mask) << i;
+            i += 2;
+        }
+        if i < hashes.len() {
+            let res_last = Self::check_unaligned(
+                buf.add(*offsets.get_unchecked(i)),
+                *hashes.get_unchecked(i) as u32,
+            );
+            res |= (res_last as u16) << i;
+        }
+        res
+    }
+
+    /// Inserts the hashes of a chunk two at a time.
+    /// Pairs that fall into the same bucket are inserted one by one so the second one sees the
+    /// first one.
+    #[target_feature(enable = "avx512f,avx512vl")]
+    #[inline]
+    unsafe fn insert_chunk(buf: *mut u8, offsets: &[usize], hashes: &[u64]) -> usize {
+        let mut num_present = 0;
+        let mut i = 0;
+        while i + 1 < hashes.len() {
+            let a = buf.add(*offsets.get_unchecked(i));
+            let b = buf.add(*offsets.get_unchecked(i + 1));
+            let (hash_a, hash_b) = (
+                *hashes.get_unchecked(i) as u32,
+                *hashes.get_unchecked(i + 1) as u32,
+            );
+            if a == b {
+                num_present += Self::insert_unaligned_bucket(a, hash_a) as usize;
+                num_present += Self::insert_unaligned_bucket(b, hash_b) as usize;
+            } else {
+                let mask = Self::make_mask_pair(hash_a, hash_b);
+                let val = Self::load_pair(a, b);
+                num_present += Self::check_pair(val, mask).count_ones() as usize;
+                let val = _mm512_or_si512(val, mask);
+                _mm256_storeu_si256(a as *mut __m256i, _mm512_castsi512_si256(val));
+                _mm256_storeu_si256(b as *mut __m256i, _mm512_extracti64x4_epi64(val, 1));
+            }
+            i += 2;
+        }
+        if i < hashes.len() {
+            num_present += Self::insert_unaligned_bucket(
+                buf.add(*offsets.get_unchecked(i)),
+                *hashes.get_unchecked(i) as u32,
+            ) as usize;
+        }
+        num_present
+    }
+}
+
+impl FilterImpl for Avx512Filter {
+    #[target_feature(enable = "avx512f,avx512vl")]
+    #[inline]
+    unsafe fn contains(&self, buf: *const u8, num_buckets: usize, hash: u64) -> bool {
+        let bucket_idx = super::bucket_index(num_buckets, hash);
+        let mask = Self::make_mask(hash as u32);
+        let bucket = (buf as *const __m256i).add(bucket_idx);
+        Self::check(_mm256_load_si256(bucket), mask)
+    }
+    #[target_feature(enable = "avx512f,avx512vl")]
+    #[inline]
+    unsafe fn insert(&self, buf: *mut u8, num_buckets: usize, hash: u64) -> bool {
+        let bucket_idx = super::bucket_index(num_buckets, hash);
+        let mask = Self::make_mask(hash as u32);
+        let bucket = (buf as *mut __m256i).add(bucket_idx);
+        let val = _mm256_load_si256(bucket);
+        let res = Self::check(val, mask);
+        _mm256_store_si256(bucket, _mm256_or_si256(val, mask));
+        res
+    }
+    #[target_feature(enable = "avx512f,avx512vl")]
+    #[inline]
+    unsafe fn contains_unaligned(&self, buf: *const u8, num_buckets: usize, hash: u64) -> bool {
+        let bucket_idx = super::bucket_index(num_buckets, hash);
+        Self::check_unaligned(buf.add(bucket_idx * 32), hash as u32)
+    }
+    #[target_feature(enable = "avx512f,avx512vl")]
+    #[inline]
+    unsafe fn insert_unaligned(&self, buf: *mut u8, num_buckets: usize, hash: u64) -> bool {
+        let bucket_idx = super::bucket_index(num_buckets, hash);
+        Self::insert_unaligned_bucket(buf.add(bucket_idx * 32), hash as u32)
+    }
+    #[target_feature(enable = "avx512f,avx512vl")]
+    unsafe fn insert_batch(&self, buf: *mut u8, num_buckets: usize, hashes: &[u64]) -> usize {
+        super::insert_batch(buf, num_buckets, hashes, |offsets, hashes| {
+            Self::insert_chunk(buf, offsets, hashes)
+        })
+    }
+    #[target_feature(enable = "avx512f,avx512vl")]
+    unsafe fn contains_batch(
+        &self,
+        buf: *const u8,
+        num_buckets: usize,
+        hashes: &[u64],
+        out: &mut [bool],
+    ) {
+        super::contains_batch(buf, num_buckets, hashes, out, |offsets, hashes| {
+            Self::check_chunk(buf, offsets, hashes)
+        })
+    }
+    #[target_feature(enable = "avx512f,avx512vl")]
+    unsafe fn contains_batch_bitmask(
+        &self,
+        buf: *const u8,
+        num_buckets: usize,
+        hashes: &[u64],
+        out: &mut [u64],
+    ) {
+        super::contains_batch_bitmask(buf, num_buckets, hashes, out, |offsets, hashes| {
+            Self::check_chunk(buf, offsets, hashes)
+        })
+    }
+    #[target_feature(enable = "avx512f,avx512vl")]
+    unsafe fn union(&self, dst: *mut u8, src: *const u8, num_buckets: usize) {
+        let mut i = 0;
+        while i + 2 <= num_buckets {
+            let dst = dst.add(i * 32) as *mut __m512i;
+            let src = src.add(i * 32) as *const __m512i;
+            _mm512_storeu_si512(
+                dst,
+                _mm512_or_si512(_mm512_loadu_si512(dst), _mm512_loadu_si512(src)),
+            );
+            i += 2;
+        }
+        if i < num_buckets {
+            Avx2Filter.union(dst.add(i * 32), src.add(i * 32), 1);
+        }
+    }
+    #[target_feature(enable = "avx512f,avx512vl")]
+    unsafe fn intersect(&self, dst: *mut u8, src: *const u8, num_buckets: usize) {
+        let mut i = 0;
+        while i + 2 <= num_buckets {
+            let dst = dst.add(i * 32) as *mut __m512i;
+            let src = src.add(i * 32) as *const __m512i;
+            _mm512_storeu_si512(
+                dst,
+                _mm512_and_si512(_mm512_loadu_si512(dst), _mm512_loadu_si512(src)),
+            );
+            i += 2;
+        }
+        if i < num_buckets {
+            Avx2Filter.intersect(dst.add(i * 32), src.add(i * 32), 1);
+        }
+    }
+    #[target_feature(enable = "avx512f,avx512vl")]
+    unsafe fn is_subset(&self, a: *const u8, b: *const u8, num_buckets: usize) -> bool {
+        let mut i = 0;
+        while i + 2 <= num_buckets {
+            let a = _mm512_loadu_si512(a.add(i * 32) as *const __m512i);
+            let b = _mm512_loadu_si512(b.add(i * 32) as *const __m512i);
+            // bits of `a` that aren't in `b`
+            if _mm512_test_epi64_mask(a, _mm512_andnot_si512(b, a)) != 0 {
+                return false;
+            }
+            i += 2;
+        }
+        i == num_buckets || Avx2Filter.is_subset(a.add(i * 32), b.add(i * 32), 1)
+    }
+    #[target_feature(enable = "avx512f,avx512vl")]
+    unsafe fn contains_any(
+        &self,
+        buf: *const u8,
+        num_buckets: usize,
+        num_filters: usize,
+        hash: u64,
+    ) -> bool {
+        let mask = Self::make_mask(hash as u32);
+        super::contains_any(buf, num_buckets, num_filters, hash, |bucket| {
+            Self::check(_mm256_loadu_si256(bucket as *const __m256i), mask)
+        })
+    }
+    /// Uses the AVX2 kernel on purpose. This backend only requires avx512f and avx512vl, a
+    /// 512 bit popcount needs avx512vpopcntdq or the byte shuffles of avx512bw, and the AVX2
+    /// kernel already keeps up with memory bandwidth on big filters.
+    #[target_feature(enable = "avx512f,avx512vl")]
+    unsafe fn count_ones(&self, buf: *const u8, num_buckets: usize) -> u64 {
+        Avx2Filter.count_ones(buf, num_buckets)
+    }
+    /// Uses the AVX2 kernel on purpose, see [Avx512Filter::count_ones].
+    #[target_feature(enable = "avx512f,avx512vl")]
+    unsafe fn count_ones_union(&self, a: *const u8, b: *const u8, num_buckets: usize) -> u64 {
+        Avx2Filter.count_ones_union(a, b, num_buckets)
+    }
+    fn backend(&self) -> Backend {
+        Backend::Avx512
+    }
+}
 
 pub struct Avx2Filter;
 
+/// Maximum number of buckets the vertical kernel of [Avx2Filter] can handle.
+/// Word indexes of the buckets are gathered using i32 offsets.
+const MAX_GATHER_BUCKETS: usize = 1 << 28;
+
 impl Avx2Filter {
     #[target_feature(enable = "avx2")]
     #[inline]
@@ -39,32 +355,255 @@ impl Avx2Filter {
         acc = _mm256_srli_epi32(acc, 27);
         _mm256_sllv_epi32(_mm256_set1_epi32(1), acc)
     }
+
+    #[target_feature(enable = "avx2")]
+    #[inline]
+    unsafe fn check_unaligned(bucket: *const u8, hash: u32) -> bool {
+        let mask = Self::make_mask(hash);
+        _mm256_testc_si256(_mm256_loadu_si256(bucket as *const __m256i), mask) != 0
+    }
+
+    /// Checks 8 hashes at once, with one hash per lane instead of one bucket per register.
+    /// Returns a mask with bit `i` set if `hashes[i]` is in the filter.
+    ///
+    /// Bucket indexes are computed with a vectorized fastrange and word `w` of each bucket is
+    /// gathered and tested against bit `w` of each mask in the same step.
+    /// `num_buckets` can't be bigger than [MAX_GATHER_BUCKETS].
+    #[target_feature(enable = "avx2")]
+    #[inline]
+    unsafe fn check_vertical(buf: *const u8, num_buckets: usize, hashes: *const u64) -> u8 {
+        let a = _mm256_loadu_si256(hashes as *const __m256i);
+        let b = _mm256_loadu_si256(hashes.add(4) as *const __m256i);
+
+        // Collects lower halves of the 64 bit lanes of `a` and `b` into one register, in order.
+        let order = _mm256_setr_epi32(0, 2, 4, 6, 1, 3, 5, 7);
+        let pack = |a: __m256i, b: __m256i| {
+            _mm256_permutevar8x32_epi32(
+                _mm256_blend_epi32(a, _mm256_slli_epi64(b, 32), 0b10101010),
+                order,
+            )
+        };
+
+        // fastrange_32 of the upper 32 bits of each hash.
+        let n = _mm256_set1_epi64x(num_buckets as i64);
+        let idx_a = _mm256_srli_epi64(_mm256_mul_epu32(_mm256_srli_epi64(a, 32), n), 32);
+        let idx_b = _mm256_srli_epi64(_mm256_mul_epu32(_mm256_srli_epi64(b, 32), n), 32);
+        let word_idx = _mm256_slli_epi32(pack(idx_a, idx_b), 3);
+
+        let hash = pack(a, b);
+        let one = _mm256_set1_epi32(1);
+        let zero = _mm256_setzero_si256();
+        let mut missing = zero;
+        for (i, &salt) in SALT.iter().enumerate() {
+            let bit = _mm256_sllv_epi32(
+                one,
+                _mm256_srli_epi32(_mm256_mullo_epi32(hash, _mm256_set1_epi32(salt as i32)), 27),
+            );
+            let words = _mm256_i32gather_epi32(
+                buf as *const i32,
+                _mm256_add_epi32(word_idx, _mm256_set1_epi32(i as i32)),
+                4,
+            );
+            missing = _mm256_or_si256(
+                missing,
+                _mm256_cmpeq_epi32(_mm256_and_si256(words, bit), zero),
+            );
+        }
+
+        !(_mm256_movemask_ps(_mm256_castsi256_ps(missing)) as u8)
+    }
+
+    #[target_feature(enable = "avx2")]
+    #[inline]
+    unsafe fn check_chunk(
+        buf: *const u8,
+        num_buckets: usize,
+        offsets: &[usize],
+        hashes: &[u64],
+    ) -> u16 {
+        let mut res = 0;
+        let mut i = 0;
+        if num_buckets <= MAX_GATHER_BUCKETS {
+            while i + 8 <= hashes.len() {
+                res |= (Self::check_vertical(buf, num_buckets, hashes.as_ptr().add(i)) as u16) << i;
+                i += 8;
+            }
+        }
+        if i < hashes.len() {
+            res |= super::check_each(
+                buf,
+                offsets.get_unchecked(i..),
+                hashes.get_unchecked(i..),
+                |bucket, hash| Self::check_unaligned(bucket, hash),
+            ) << i;
+        }
+        res
+    }
+
+    #[target_feature(enable = "avx2")]
+    #[inline]
+    unsafe fn insert_unaligned_bucket(bucket: *mut u8, hash: u32) -> bool {
+        let mask = Self::make_mask(hash);
+        let bucket = bucket as *mut __m256i;
+        let val = _mm256_loadu_si256(bucket);
+        let res = _mm256_testc_si256(val, mask) != 0;
+        _mm256_storeu_si256(bucket, _mm256_or_si256(val, mask));
+        res
+    }
+
+    /// Number of set bits in each byte of `v`, looked up a nibble at a time.
+    #[target_feature(enable = "avx2")]
+    #[inline]
+    unsafe fn popcount_bytes(v: __m256i) -> __m256i {
+        let lut = _mm256_setr_epi8(
+            0, 1, 1, 2, 1, 2, 2, 3, 1, 2, 2, 3, 2, 3, 3, 4, 0, 1, 1, 2, 1, 2, 2, 3, 1, 2, 2, 3, 2,
+            3, 3, 4,
+        );
+        let low = _mm256_set1_epi8(0x0f);
+        let lo = _mm256_shuffle_epi8(lut, _mm256_and_si256(v, low));
+        let hi = _mm256_shuffle_epi8(lut, _mm256_and_si256(_mm256_srli_epi16(v, 4), low));
+        _mm256_add_epi8(lo, hi)
+    }
+
+    /// Sums the set bits of the `num_vectors` vectors returned by `load`.
+    #[target_feature(enable = "avx2")]
+    #[inline]
+    unsafe fn sum_popcounts(num_vectors: usize, load: impl Fn(usize) -> __m256i) -> u64 {
+        let mut acc = _mm256_setzero_si256();
+        for i in 0..num_vectors {
+            let ones = Self::popcount_bytes(load(i));
+            acc = _mm256_add_epi64(acc, _mm256_sad_epu8(ones, _mm256_setzero_si256()));
+        }
+        let mut sums = [0u64; 4];
+        _mm256_storeu_si256(sums.as_mut_ptr() as *mut __m256i, acc);
+        sums.iter().sum()
+    }
 }
 
 impl FilterImpl for Avx2Filter {
     #[target_feature(enable = "avx2")]
     #[inline]
     unsafe fn contains(&self, buf: *const u8, num_buckets: usize, hash: u64) -> bool {
-        let bucket_idx =
-            fastrange_rs::fastrange_32(hash.rotate_left(32) as u32, num_buckets as u32);
+        let bucket_idx = super::bucket_index(num_buckets, hash);
         let mask = Self::make_mask(hash as u32);
-        let bucket = (buf as *const __m256i).add(bucket_idx as usize);
+        let bucket = (buf as *const __m256i).add(bucket_idx);
         _mm256_testc_si256(_mm256_load_si256(bucket), mask) != 0
     }
     #[target_feature(enable = "avx2")]
     #[inline]
     unsafe fn insert(&self, buf: *mut u8, num_buckets: usize, hash: u64) -> bool {
-        let bucket_idx =
-            fastrange_rs::fastrange_32(hash.rotate_left(32) as u32, num_buckets as u32);
+        let bucket_idx = super::bucket_index(num_buckets, hash);
         let mask = Self::make_mask(hash as u32);
-        let bucket = (buf as *mut __m256i).add(bucket_idx as usize);
+        let bucket = (buf as *mut __m256i).add(bucket_idx);
         let val = _mm256_load_si256(bucket);
         let res = _mm256_testc_si256(val, mask) != 0;
         _mm256_store_si256(bucket, _mm256_or_si256(val, mask));
         res
     }
-    fn which(&self) -> &'static str {
-        "Avx2Filter"
+    #[target_feature(enable = "avx2")]
+    #[inline]
+    unsafe fn contains_unaligned(&self, buf: *const u8, num_buckets: usize, hash: u64) -> bool {
+        let bucket_idx = super::bucket_index(num_buckets, hash);
+        Self::check_unaligned(buf.add(bucket_idx * 32), hash as u32)
+    }
+    #[target_feature(enable = "avx2")]
+    #[inline]
+    unsafe fn insert_unaligned(&self, buf: *mut u8, num_buckets: usize, hash: u64) -> bool {
+        let bucket_idx = super::bucket_index(num_buckets, hash);
+        Self::insert_unaligned_bucket(buf.add(bucket_idx * 32), hash as u32)
+    }
+    #[target_feature(enable = "avx2")]
+    unsafe fn insert_batch(&self, buf: *mut u8, num_buckets: usize, hashes: &[u64]) -> usize {
+        super::insert_batch(buf, num_buckets, hashes, |offsets, hashes| {
+            super::insert_each(buf, offsets, hashes, |bucket, hash| {
+                Self::insert_unaligned_bucket(bucket, hash)
+            })
+        })
+    }
+    #[target_feature(enable = "avx2")]
+    unsafe fn contains_batch(
+        &self,
+        buf: *const u8,
+        num_buckets: usize,
+        hashes: &[u64],
+        out: &mut [bool],
+    ) {
+        super::contains_batch(buf, num_buckets, hashes, out, |offsets, hashes| {
+            Self::check_chunk(buf, num_buckets, offsets, hashes)
+        })
+    }
+    #[target_feature(enable = "avx2")]
+    unsafe fn contains_batch_bitmask(
+        &self,
+        buf: *const u8,
+        num_buckets: usize,
+        hashes: &[u64],
+        out: &mut [u64],
+    ) {
+        super::contains_batch_bitmask(buf, num_buckets, hashes, out, |offsets, hashes| {
+            Self::check_chunk(buf, num_buckets, offsets, hashes)
+        })
+    }
+    #[target_feature(enable = "avx2")]
+    unsafe fn union(&self, dst: *mut u8, src: *const u8, num_buckets: usize) {
+        for i in 0..num_buckets {
+            let dst = (dst as *mut __m256i).add(i);
+            let src = (src as *const __m256i).add(i);
+            _mm256_storeu_si256(
+                dst,
+                _mm256_or_si256(_mm256_loadu_si256(dst), _mm256_loadu_si256(src)),
+            );
+        }
+    }
+    #[target_feature(enable = "avx2")]
+    unsafe fn intersect(&self, dst: *mut u8, src: *const u8, num_buckets: usize) {
+        for i in 0..num_buckets {
+            let dst = (dst as *mut __m256i).add(i);
+            let src = (src as *const __m256i).add(i);
+            _mm256_storeu_si256(
+                dst,
+                _mm256_and_si256(_mm256_loadu_si256(dst), _mm256_loadu_si256(src)),
+            );
+        }
+    }
+    #[target_feature(enable = "avx2")]
+    unsafe fn is_subset(&self, a: *const u8, b: *const u8, num_buckets: usize) -> bool {
+        (0..num_buckets).all(|i| {
+            let a = _mm256_loadu_si256((a as *const __m256i).add(i));
+            let b = _mm256_loadu_si256((b as *const __m256i).add(i));
+            _mm256_testc_si256(b, a) != 0
+        })
+    }
+    #[target_feature(enable = "avx2")]
+    unsafe fn contains_any(
+        &self,
+        buf: *const u8,
+        num_buckets: usize,
+        num_filters: usize,
+        hash: u64,
+    ) -> bool {
+        let mask = Self::make_mask(hash as u32);
+        super::contains_any(buf, num_buckets, num_filters, hash, |bucket| {
+            _mm256_testc_si256(_mm256_loadu_si256(bucket as *const __m256i), mask) != 0
+        })
+    }
+    #[target_feature(enable = "avx2")]
+    unsafe fn count_ones(&self, buf: *const u8, num_buckets: usize) -> u64 {
+        Self::sum_popcounts(num_buckets, |i| {
+            _mm256_loadu_si256((buf as *const __m256i).add(i))
+        })
+    }
+    #[target_feature(enable = "avx2")]
+    unsafe fn count_ones_union(&self, a: *const u8, b: *const u8, num_buckets: usize) -> u64 {
+        Self::sum_popcounts(num_buckets, |i| {
+            _mm256_or_si256(
+                _mm256_loadu_si256((a as *const __m256i).add(i)),
+                _mm256_loadu_si256((b as *const __m256i).add(i)),
+            )
+        })
+    }
+    fn backend(&self) -> Backend {
+        Backend::Avx2
     }
 }
 
@@ -102,71 +641,192 @@ impl SseFilter {
         acc = (_mm_srli_epi32(acc.0, 27), _mm_srli_epi32(acc.1, 27));
         (Self::power_of_two(acc.0), Self::power_of_two(acc.1))
     }
+
+    #[target_feature(enable = "sse4.1")]
+    #[inline]
+    unsafe fn check_unaligned(bucket: *const u8, hash: u32) -> bool {
+        let mask = Self::make_mask(hash);
+        let bucket = bucket as *const __m128i;
+        _mm_testc_si128(_mm_loadu_si128(bucket), mask.0) != 0
+            && _mm_testc_si128(_mm_loadu_si128(bucket.add(1)), mask.1) != 0
+    }
+
+    #[target_feature(enable = "sse4.1")]
+    #[inline]
+    unsafe fn insert_unaligned_bucket(bucket: *mut u8, hash: u32) -> bool {
+        let mask = Self::make_mask(hash);
+        let bucket = bucket as *mut __m128i;
+        let val = (_mm_loadu_si128(bucket), _mm_loadu_si128(bucket.add(1)));
+        let res = _mm_testc_si128(val.0, mask.0) != 0 && _mm_testc_si128(val.1, mask.1) != 0;
+        _mm_storeu_si128(bucket, _mm_or_si128(val.0, mask.0));
+        _mm_storeu_si128(bucket.add(1), _mm_or_si128(val.1, mask.1));
+        res
+    }
+
+    /// Number of set bits in each byte of `v`, looked up a nibble at a time.
+    #[target_feature(enable = "sse4.1")]
+    #[inline]
+    unsafe fn popcount_bytes(v: __m128i) -> __m128i {
+        let lut = _mm_setr_epi8(0, 1, 1, 2, 1, 2, 2, 3, 1, 2, 2, 3, 2, 3, 3, 4);
+        let low = _mm_set1_epi8(0x0f);
+        let lo = _mm_shuffle_epi8(lut, _mm_and_si128(v, low));
+        let hi = _mm_shuffle_epi8(lut, _mm_and_si128(_mm_srli_epi16(v, 4), low));
+        _mm_add_epi8(lo, hi)
+    }
+
+    /// Sums the set bits of the `num_vectors` vectors returned by `load`.
+    #[target_feature(enable = "sse4.1")]
+    #[inline]
+    unsafe fn sum_popcounts(num_vectors: usize, load: impl Fn(usize) -> __m128i) -> u64 {
+        let mut acc = _mm_setzero_si128();
+        for i in 0..num_vectors {
+            let ones = Self::popcount_bytes(load(i));
+            acc = _mm_add_epi64(acc, _mm_sad_epu8(ones, _mm_setzero_si128()));
+        }
+        let mut sums = [0u64; 2];
+        _mm_storeu_si128(sums.as_mut_ptr() as *mut __m128i, acc);
+        sums.iter().sum()
+    }
 }
 
 impl FilterImpl for SseFilter {
     #[target_feature(enable = "sse4.1")]
     #[inline]
     unsafe fn contains(&self, buf: *const u8, num_buckets: usize, hash: u64) -> bool {
-        let bucket_idx =
-            fastrange_rs::fastrange_32(hash.rotate_left(32) as u32, num_buckets as u32);
-        let mask = Self::make_mask(hash as u32);
-        let bucket = (buf as *const __m128i).add((bucket_idx * 2) as usize);
-        _mm_testc_si128(*bucket, mask.0) != 0 && _mm_testc_si128(*bucket.add(1), mask.1) != 0
+        // unaligned loads of aligned data cost the same as aligned loads since Nehalem, so both
+        // paths share one bucket implementation
+        self.contains_unaligned(buf, num_buckets, hash)
     }
     #[target_feature(enable = "sse4.1")]
     #[inline]
     unsafe fn insert(&self, buf: *mut u8, num_buckets: usize, hash: u64) -> bool {
-        let bucket_idx =
-            fastrange_rs::fastrange_32(hash.rotate_left(32) as u32, num_buckets as u32);
-        let mask = Self::make_mask(hash as u32);
-        let bucket = (buf as *mut __m128i).add((bucket_idx * 2) as usize);
-        _mm_storeu_si128(bucket, _mm_or_si128(*bucket, mask.0));
-        let res =
-            _mm_testc_si128(*bucket, mask.0) != 0 && _mm_testc_si128(*bucket.add(1), mask.1) != 0;
-        _mm_storeu_si128(bucket.add(1), _mm_or_si128(*bucket.add(1), mask.1));
-        res
+        self.insert_unaligned(buf, num_buckets, hash)
     }
-    fn which(&self) -> &'static str {
-        "SseFilter"
+    #[target_feature(enable = "sse4.1")]
+    #[inline]
+    unsafe fn contains_unaligned(&self, buf: *const u8, num_buckets: usize, hash: u64) -> bool {
+        let bucket_idx = super::bucket_index(num_buckets, hash);
+        Self::check_unaligned(buf.add(bucket_idx * 32), hash as u32)
     }
-}
-
-#[cfg(test)]
-mod test {
-    use super::*;
-    use crate::ALIGNMENT;
-    use std::alloc::{alloc_zeroed, dealloc, Layout};
-
-    struct Buf {
-        ptr: *mut u8,
-        layout: Layout,
+    #[target_feature(enable = "sse4.1")]
+    #[inline]
+    unsafe fn insert_unaligned(&self, buf: *mut u8, num_buckets: usize, hash: u64) -> bool {
+        let bucket_idx = super::bucket_index(num_buckets, hash);
+        Self::insert_unaligned_bucket(buf.add(bucket_idx * 32), hash as u32)
     }
-
-    impl Buf {
-        fn new(len: usize) -> Self {
-            let layout = Layout::from_size_align(len, ALIGNMENT).unwrap();
-            let ptr = unsafe { alloc_zeroed(layout) };
-
-            Self { layout, ptr }
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn insert_batch(&self, buf: *mut u8, num_buckets: usize, hashes: &[u64]) -> usize {
+        super::insert_batch(buf, num_buckets, hashes, |offsets, hashes| {
+            super::insert_each(buf, offsets, hashes, |bucket, hash| {
+                Self::insert_unaligned_bucket(bucket, hash)
+            })
+        })
+    }
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn contains_batch(
+        &self,
+        buf: *const u8,
+        num_buckets: usize,
+        hashes: &[u64],
+        out: &mut [bool],
+    ) {
+        super::contains_batch(buf, num_buckets, hashes, out, |offsets, hashes| {
+            super::check_each(buf, offsets, hashes, |bucket, hash| {
+                Self::check_unaligned(bucket, hash)
+            })
+        })
+    }
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn contains_batch_bitmask(
+        &self,
+        buf: *const u8,
+        num_buckets: usize,
+        hashes: &[u64],
+        out: &mut [u64],
+    ) {
+        super::contains_batch_bitmask(buf, num_buckets, hashes, out, |offsets, hashes| {
+            super::check_each(buf, offsets, hashes, |bucket, hash| {
+                Self::check_unaligned(bucket, hash)
+            })
+        })
+    }
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn union(&self, dst: *mut u8, src: *const u8, num_buckets: usize) {
+        for i in 0..num_buckets * 2 {
+            let dst = (dst as *mut __m128i).add(i);
+            let src = (src as *const __m128i).add(i);
+            _mm_storeu_si128(
+                dst,
+                _mm_or_si128(_mm_loadu_si128(dst), _mm_loadu_si128(src)),
+            );
         }
     }
-
-    impl Drop for Buf {
-        fn drop(&mut self) {
-            unsafe {
-                dealloc(self.ptr, self.layout);
-            }
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn intersect(&self, dst: *mut u8, src: *const u8, num_buckets: usize) {
+        for i in 0..num_buckets * 2 {
+            let dst = (dst as *mut __m128i).add(i);
+            let src = (src as *const __m128i).add(i);
+            _mm_storeu_si128(
+                dst,
+                _mm_and_si128(_mm_loadu_si128(dst), _mm_loadu_si128(src)),
+            );
         }
     }
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn is_subset(&self, a: *const u8, b: *const u8, num_buckets: usize) -> bool {
+        (0..num_buckets * 2).all(|i| {
+            let a = _mm_loadu_si128((a as *const __m128i).add(i));
+            let b = _mm_loadu_si128((b as *const __m128i).add(i));
+            _mm_testc_si128(b, a) != 0
+        })
+    }
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn contains_any(
+        &self,
+        buf: *const u8,
+        num_buckets: usize,
+        num_filters: usize,
+        hash: u64,
+    ) -> bool {
+        let mask = Self::make_mask(hash as u32);
+        super::contains_any(buf, num_buckets, num_filters, hash, |bucket| {
+            let bucket = bucket as *const __m128i;
+            _mm_testc_si128(_mm_loadu_si128(bucket), mask.0) != 0
+                && _mm_testc_si128(_mm_loadu_si128(bucket.add(1)), mask.1) != 0
+        })
+    }
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn count_ones(&self, buf: *const u8, num_buckets: usize) -> u64 {
+        Self::sum_popcounts(num_buckets * 2, |i| {
+            _mm_loadu_si128((buf as *const __m128i).add(i))
+        })
+    }
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn count_ones_union(&self, a: *const u8, b: *const u8, num_buckets: usize) -> u64 {
+        Self::sum_popcounts(num_buckets * 2, |i| {
+            _mm_or_si128(
+                _mm_loadu_si128((a as *const __m128i).add(i)),
+                _mm_loadu_si128((b as *const __m128i).add(i)),
+            )
+        })
+    }
+    fn backend(&self) -> Backend {
+        Backend::Sse41
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod test {
+    use super::*;
+    use crate::Sbbf;
 
     #[test]
     fn smoke_test_sse() {
+        let mut filter = Sbbf::new(2);
+        let buf = filter.as_bytes_mut().as_mut_ptr();
         unsafe {
-            let buf = Buf::new(64);
-
-            assert!(!SseFilter.insert(buf.ptr, 2, 69));
-            assert!(SseFilter.contains(buf.ptr, 2, 69));
+            assert!(!SseFilter.insert(buf, 2, 69));
+            assert!(SseFilter.contains(buf, 2, 69));
         }
     }
 }
@@ -0,0 +1,149 @@
+use alloc::alloc::{alloc_zeroed, handle_alloc_error, Layout};
+use alloc::boxed::Box;
+use core::fmt;
+use core::ptr;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::{arch, ParquetValue, Sbbf, BUCKET_SIZE, MAX_NUM_BUCKETS};
+
+const WORDS: usize = BUCKET_SIZE / 4;
+
+/// Split block bloom filter that can be inserted into from many threads at the same time.
+///
+/// Each word of a bucket is set with an atomic `fetch_or` so concurrent inserts never lose bits
+/// and the filter ends up with the same bits as an [Sbbf] that had the same hashes inserted,
+/// regardless of the order of the inserts.
+///
+/// All atomic operations use [Ordering::Relaxed]. An insert is visible to [AtomicSbbf::contains]
+/// on another thread once that thread synchronizes with the inserting thread, for example by
+/// joining it.
+pub struct AtomicSbbf {
+    words: Box<[AtomicU32]>,
+}
+
+impl AtomicSbbf {
+    /// Allocates an empty filter with `num_buckets` buckets.
+    /// # Panics
+    /// Panics if `num_buckets` is zero, bigger than [MAX_NUM_BUCKETS] or the buffer size overflows.
+    pub fn new(num_buckets: usize) -> Self {
+        assert!(num_buckets > 0, "num_buckets has to be bigger than zero");
+        assert!(
+            num_buckets <= MAX_NUM_BUCKETS,
+            "num_buckets can't be bigger than MAX_NUM_BUCKETS"
+        );
+        let len = num_buckets
+            .checked_mul(WORDS)
+            .expect("filter size overflows usize");
+
+        // zeroed allocation is much faster than pushing atomics one by one for big filters,
+        // the layout is the one Box<[AtomicU32]> frees with
+        let layout = Layout::array::<AtomicU32>(len).expect("filter size overflows usize");
+        let ptr = unsafe { alloc_zeroed(layout) } as *mut AtomicU32;
+        if ptr.is_null() {
+            handle_alloc_error(layout);
+        }
+        // all zero bytes is a valid AtomicU32
+        let words = unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(ptr, len)) };
+
+        Self { words }
+    }
+
+    /// Check if filter contains `hash`.
+    #[inline]
+    pub fn contains(&self, hash: u64) -> bool {
+        let bucket = self.bucket(hash);
+        let mask = arch::bucket_mask(hash as u32);
+        bucket
+            .iter()
+            .zip(mask)
+            .all(|(word, mask)| word.load(Ordering::Relaxed) & mask != 0)
+    }
+
+    /// Insert `hash` into the filter.
+    /// Returns true if `hash` was already in the filter.
+    ///
+    /// The returned flag is only as precise as relaxed atomics allow. It is true if every bit
+    /// of `hash` was already set when this call looked at it, so if multiple threads insert
+    /// hashes that share bits at the same time, all of them can return false.
+    /// Summing the flags doesn't give a deterministic count of duplicates.
+    #[inline]
+    pub fn insert(&self, hash: u64) -> bool {
+        let bucket = self.bucket(hash);
+        let mask = arch::bucket_mask(hash as u32);
+        let mut res = true;
+        for (word, mask) in bucket.iter().zip(mask) {
+            let mut prev = word.load(Ordering::Relaxed);
+            // avoid taking the cache line exclusively if the bit is already set
+            if prev & mask == 0 {
+                prev = word.fetch_or(mask, Ordering::Relaxed);
+            }
+            res &= prev & mask != 0;
+        }
+        res
+    }
+
+    /// Check if filter contains `value`, hashed by its parquet plain encoding.
+    #[inline]
+    pub fn contains_value<T: ParquetValue + ?Sized>(&self, value: &T) -> bool {
+        self.contains(value.parquet_hash())
+    }
+
+    /// Insert `value` into the filter, hashed by its parquet plain encoding.
+    /// Returns true if `value` was already in the filter, see [AtomicSbbf::insert].
+    #[inline]
+    pub fn insert_value<T: ParquetValue + ?Sized>(&self, value: &T) -> bool {
+        self.insert(value.parquet_hash())
+    }
+
+    /// Returns the number of buckets in the filter.
+    pub fn num_buckets(&self) -> usize {
+        self.words.len() / WORDS
+    }
+
+    /// Copies the filter bits into an [Sbbf].
+    ///
+    /// Inserts that run at the same time as this might be partially copied.
+    pub fn to_sbbf(&self) -> Sbbf {
+        let mut filter = Sbbf::new(self.num_buckets());
+        for (bytes, word) in filter
+            .as_bytes_mut()
+            .chunks_exact_mut(4)
+            .zip(self.words.iter())
+        {
+            bytes.copy_from_slice(&word.load(Ordering::Relaxed).to_le_bytes());
+        }
+        filter
+    }
+
+    fn bucket(&self, hash: u64) -> &[AtomicU32] {
+        let start = arch::bucket_index(self.num_buckets(), hash) * WORDS;
+        &self.words[start..start + WORDS]
+    }
+}
+
+impl From<&Sbbf> for AtomicSbbf {
+    fn from(filter: &Sbbf) -> Self {
+        let atomic = Self::new(filter.num_buckets());
+        for (word, bytes) in atomic.words.iter().zip(filter.as_bytes().chunks_exact(4)) {
+            word.store(
+                u32::from_le_bytes(bytes.try_into().unwrap()),
+                Ordering::Relaxed,
+            );
+        }
+        atomic
+    }
+}
+
+impl From<&AtomicSbbf> for Sbbf {
+    fn from(filter: &AtomicSbbf) -> Self {
+        filter.to_sbbf()
+    }
+}
+
+impl fmt::Debug for AtomicSbbf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AtomicSbbf")
+            .field("num_buckets", &self.num_buckets())
+            .finish()
+    }
+}
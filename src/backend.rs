@@ -0,0 +1,57 @@
+use core::fmt;
+
+/// Filter implementations that [FilterFn](crate::FilterFn) can use.
+///
+/// Every backend gives the same results, they only differ in speed.
+/// See [FilterFn::available_backends](crate::FilterFn::available_backends) for the ones
+/// the current cpu supports.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Backend {
+    /// AVX-512 (avx512f and avx512vl) on x86 and x86_64.
+    Avx512,
+    /// AVX2 on x86 and x86_64.
+    Avx2,
+    /// SSE4.1 on x86 and x86_64.
+    Sse41,
+    /// NEON on little endian aarch64.
+    Neon,
+    /// SIMD128 on wasm, has to be enabled at compile time.
+    Wasm,
+    /// Vector extension (V) on riscv64 linux.
+    Rvv,
+    /// Portable implementation that works everywhere.
+    Fallback,
+}
+
+impl Backend {
+    /// All backends, from the most preferred one to the least.
+    pub(crate) const ALL: [Backend; 7] = [
+        Backend::Avx512,
+        Backend::Avx2,
+        Backend::Sse41,
+        Backend::Neon,
+        Backend::Wasm,
+        Backend::Rvv,
+        Backend::Fallback,
+    ];
+
+    /// Returns the name of the filter implementation, same as [FilterFn::which](crate::FilterFn::which).
+    pub fn name(&self) -> &'static str {
+        match self {
+            Backend::Avx512 => "Avx512Filter",
+            Backend::Avx2 => "Avx2Filter",
+            Backend::Sse41 => "SseFilter",
+            Backend::Neon => "NeonFilter",
+            Backend::Wasm => "WasmFilter",
+            Backend::Rvv => "RvvFilter",
+            Backend::Fallback => "FallbackFilter",
+        }
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
@@ -0,0 +1,180 @@
+use alloc::boxed::Box;
+use core::fmt;
+
+use crate::{arch, ParquetValue, Sbbf, BUCKET_SIZE, MAX_NUM_BUCKETS};
+
+const WORDS: usize = BUCKET_SIZE / 4;
+/// Each bit of a bucket has a 4 bit counter, so a 32 bit word has its counters in two u64.
+const COUNTERS_PER_U64: usize = 16;
+const BUCKET_LEN: usize = WORDS * 32 / COUNTERS_PER_U64;
+/// Counters stop at this value and aren't decremented after that.
+const MAX_COUNT: u64 = 0xf;
+
+/// Split block bloom filter that keeps a 4 bit counter for each bit, so hashes can be removed.
+///
+/// Buckets and bits are picked the same way as in an [Sbbf], so [CountingSbbf::to_sbbf] gives
+/// the same filter as inserting the hashes that weren't removed into an [Sbbf] and it can be
+/// written into parquet files.
+///
+/// Uses 4 times the memory of an [Sbbf] with the same number of buckets.
+///
+/// A counter that reaches 15 stays at 15, since it isn't known how many of the inserts that hit
+/// it were lost. Such bits can't be cleared by [CountingSbbf::remove] anymore, which only costs
+/// some false positives.
+#[derive(Clone, PartialEq, Eq)]
+pub struct CountingSbbf {
+    counters: Box<[u64]>,
+}
+
+impl CountingSbbf {
+    /// Allocates an empty filter with `num_buckets` buckets.
+    /// # Panics
+    /// Panics if `num_buckets` is zero, bigger than [MAX_NUM_BUCKETS] or the buffer size overflows.
+    pub fn new(num_buckets: usize) -> Self {
+        assert!(num_buckets > 0, "num_buckets has to be bigger than zero");
+        assert!(
+            num_buckets <= MAX_NUM_BUCKETS,
+            "num_buckets can't be bigger than MAX_NUM_BUCKETS"
+        );
+        let len = num_buckets
+            .checked_mul(BUCKET_LEN)
+            .expect("filter size overflows usize");
+
+        Self {
+            counters: alloc::vec![0; len].into_boxed_slice(),
+        }
+    }
+
+    /// Check if filter contains `hash`.
+    #[inline]
+    pub fn contains(&self, hash: u64) -> bool {
+        let bucket = self.bucket(hash);
+        counter_positions(hash)
+            .iter()
+            .all(|&(idx, shift)| (bucket[idx] >> shift) & MAX_COUNT != 0)
+    }
+
+    /// Insert `hash` into the filter.
+    /// Returns true if `hash` was already in the filter.
+    #[inline]
+    pub fn insert(&mut self, hash: u64) -> bool {
+        let bucket = self.bucket_mut(hash);
+        let mut res = true;
+        for (idx, shift) in counter_positions(hash) {
+            let count = (bucket[idx] >> shift) & MAX_COUNT;
+            res &= count != 0;
+            if count < MAX_COUNT {
+                bucket[idx] += 1 << shift;
+            }
+        }
+        res
+    }
+
+    /// Remove `hash` from the filter.
+    /// Returns false and leaves the filter as it is if the filter doesn't contain `hash`.
+    ///
+    /// Only hashes that were inserted should be removed. Removing a hash that the filter
+    /// contains because of a false positive decrements counters of other hashes, which can make
+    /// the filter return false for hashes that are in it.
+    #[inline]
+    pub fn remove(&mut self, hash: u64) -> bool {
+        if !self.contains(hash) {
+            return false;
+        }
+        let bucket = self.bucket_mut(hash);
+        for (idx, shift) in counter_positions(hash) {
+            if (bucket[idx] >> shift) & MAX_COUNT < MAX_COUNT {
+                bucket[idx] -= 1 << shift;
+            }
+        }
+        true
+    }
+
+    /// Check if filter contains `value`, hashed by its parquet plain encoding.
+    #[inline]
+    pub fn contains_value<T: ParquetValue + ?Sized>(&self, value: &T) -> bool {
+        self.contains(value.parquet_hash())
+    }
+
+    /// Insert `value` into the filter, hashed by its parquet plain encoding.
+    /// Returns true if `value` was already in the filter.
+    #[inline]
+    pub fn insert_value<T: ParquetValue + ?Sized>(&mut self, value: &T) -> bool {
+        self.insert(value.parquet_hash())
+    }
+
+    /// Remove `value` from the filter, hashed by its parquet plain encoding.
+    /// See [CountingSbbf::remove].
+    #[inline]
+    pub fn remove_value<T: ParquetValue + ?Sized>(&mut self, value: &T) -> bool {
+        self.remove(value.parquet_hash())
+    }
+
+    /// Returns the number of buckets in the filter.
+    pub fn num_buckets(&self) -> usize {
+        self.counters.len() / BUCKET_LEN
+    }
+
+    /// Returns a regular filter that has the bits with a non-zero counter set.
+    pub fn to_sbbf(&self) -> Sbbf {
+        let mut filter = Sbbf::new(self.num_buckets());
+        for (bytes, counters) in filter
+            .as_bytes_mut()
+            .chunks_exact_mut(4)
+            .zip(self.counters.chunks_exact(2))
+        {
+            let word = nonzero_counters(counters[0]) | nonzero_counters(counters[1]) << 16;
+            bytes.copy_from_slice(&word.to_le_bytes());
+        }
+        filter
+    }
+
+    fn bucket(&self, hash: u64) -> &[u64] {
+        let start = arch::bucket_index(self.num_buckets(), hash) * BUCKET_LEN;
+        &self.counters[start..start + BUCKET_LEN]
+    }
+
+    fn bucket_mut(&mut self, hash: u64) -> &mut [u64] {
+        let start = arch::bucket_index(self.num_buckets(), hash) * BUCKET_LEN;
+        &mut self.counters[start..start + BUCKET_LEN]
+    }
+}
+
+/// Returns the index in the bucket and the shift of the counter for each bit of `hash`.
+#[inline(always)]
+fn counter_positions(hash: u64) -> [(usize, u32); WORDS] {
+    let mut positions = [(0, 0); WORDS];
+    for (word, (position, mask)) in positions
+        .iter_mut()
+        .zip(arch::bucket_mask(hash as u32))
+        .enumerate()
+    {
+        let bit = mask.trailing_zeros() as usize;
+        *position = (
+            word * 2 + bit / COUNTERS_PER_U64,
+            (bit % COUNTERS_PER_U64) as u32 * 4,
+        );
+    }
+    positions
+}
+
+/// Returns a mask with bit `i` set if the `i`th counter in `counters` isn't zero.
+fn nonzero_counters(counters: u64) -> u32 {
+    (0..COUNTERS_PER_U64)
+        .filter(|i| (counters >> (i * 4)) & MAX_COUNT != 0)
+        .fold(0, |acc, i| acc | 1 << i)
+}
+
+impl From<&CountingSbbf> for Sbbf {
+    fn from(filter: &CountingSbbf) -> Self {
+        filter.to_sbbf()
+    }
+}
+
+impl fmt::Debug for CountingSbbf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CountingSbbf")
+            .field("num_buckets", &self.num_buckets())
+            .finish()
+    }
+}
@@ -0,0 +1,160 @@
+use crate::Sbbf;
+
+/// Iterator that skips items whose hash was already inserted into its filter.
+/// Created by [ApproxDedupExt::approx_dedup].
+#[derive(Debug, Clone)]
+pub struct ApproxDedup<I, H> {
+    iter: I,
+    filter: Sbbf,
+    hasher: H,
+    num_suppressed: u64,
+}
+
+impl<I, H> ApproxDedup<I, H> {
+    /// Returns the number of items that were skipped so far.
+    pub fn num_suppressed(&self) -> u64 {
+        self.num_suppressed
+    }
+
+    /// Returns the filter that has the hashes of the items seen so far.
+    pub fn filter(&self) -> &Sbbf {
+        &self.filter
+    }
+
+    /// Returns the filter, so it can be used to deduplicate another iterator.
+    pub fn into_filter(self) -> Sbbf {
+        self.filter
+    }
+}
+
+impl<I, H> Iterator for ApproxDedup<I, H>
+where
+    I: Iterator,
+    H: FnMut(&I::Item) -> u64,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        for item in self.iter.by_ref() {
+            if self.filter.insert((self.hasher)(&item)) {
+                self.num_suppressed += 1;
+            } else {
+                return Some(item);
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+}
+
+/// Extension trait that adds [approx_dedup](ApproxDedupExt::approx_dedup) to iterators.
+pub trait ApproxDedupExt: Iterator + Sized {
+    /// Returns an iterator that skips items whose hash was already inserted into `filter`,
+    /// hashing each item with `hasher`.
+    ///
+    /// Items that are a false positive of the filter are skipped too, so some items that
+    /// weren't seen before can be dropped. [Sbbf::for_ndv] can be used to size the filter
+    /// for the expected number of distinct items.
+    fn approx_dedup<H>(self, filter: Sbbf, hasher: H) -> ApproxDedup<Self, H>
+    where
+        H: FnMut(&Self::Item) -> u64,
+    {
+        ApproxDedup {
+            iter: self,
+            filter,
+            hasher,
+            num_suppressed: 0,
+        }
+    }
+}
+
+impl<I: Iterator> ApproxDedupExt for I {}
+
+#[cfg(feature = "futures")]
+mod stream {
+    use core::pin::Pin;
+    use core::task::{ready, Context, Poll};
+
+    use futures_core::Stream;
+
+    use crate::Sbbf;
+
+    /// Stream that skips items whose hash was already inserted into its filter.
+    /// Created by [ApproxDedupStreamExt::approx_dedup].
+    #[derive(Debug)]
+    pub struct ApproxDedupStream<S, H> {
+        stream: S,
+        filter: Sbbf,
+        hasher: H,
+        num_suppressed: u64,
+    }
+
+    impl<S, H> ApproxDedupStream<S, H> {
+        /// Returns the number of items that were skipped so far.
+        pub fn num_suppressed(&self) -> u64 {
+            self.num_suppressed
+        }
+
+        /// Returns the filter that has the hashes of the items seen so far.
+        pub fn filter(&self) -> &Sbbf {
+            &self.filter
+        }
+
+        /// Returns the filter, so it can be used to deduplicate another stream.
+        pub fn into_filter(self) -> Sbbf {
+            self.filter
+        }
+    }
+
+    impl<S, H> Stream for ApproxDedupStream<S, H>
+    where
+        S: Stream,
+        H: FnMut(&S::Item) -> u64,
+    {
+        type Item = S::Item;
+
+        fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+            // `stream` is pinned along with `self` and it is never moved out of it,
+            // the other fields aren't pinned.
+            let this = unsafe { self.get_unchecked_mut() };
+            let mut stream = unsafe { Pin::new_unchecked(&mut this.stream) };
+            while let Some(item) = ready!(stream.as_mut().poll_next(cx)) {
+                if this.filter.insert((this.hasher)(&item)) {
+                    this.num_suppressed += 1;
+                } else {
+                    return Poll::Ready(Some(item));
+                }
+            }
+            Poll::Ready(None)
+        }
+
+        fn size_hint(&self) -> (usize, Option<usize>) {
+            (0, self.stream.size_hint().1)
+        }
+    }
+
+    /// Extension trait that adds [approx_dedup](ApproxDedupStreamExt::approx_dedup) to streams.
+    pub trait ApproxDedupStreamExt: Stream + Sized {
+        /// Returns a stream that skips items whose hash was already inserted into `filter`.
+        /// See [ApproxDedupExt::approx_dedup](crate::ApproxDedupExt::approx_dedup).
+        fn approx_dedup<H>(self, filter: Sbbf, hasher: H) -> ApproxDedupStream<Self, H>
+        where
+            H: FnMut(&Self::Item) -> u64,
+        {
+            ApproxDedupStream {
+                stream: self,
+                filter,
+                hasher,
+                num_suppressed: 0,
+            }
+        }
+    }
+
+    impl<S: Stream> ApproxDedupStreamExt for S {}
+}
+
+#[cfg(feature = "futures")]
+pub use stream::{ApproxDedupStream, ApproxDedupStreamExt};
@@ -0,0 +1,83 @@
+use core::fmt;
+
+/// Errors returned by the safe filter API.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// Buffer length isn't a non-zero multiple of [BUCKET_SIZE](crate::BUCKET_SIZE).
+    InvalidLength(usize),
+    /// Buffer isn't aligned to [ALIGNMENT](crate::ALIGNMENT) bytes.
+    Unaligned,
+    /// Filter would have more than [MAX_NUM_BUCKETS](crate::MAX_NUM_BUCKETS) buckets, contains
+    /// the number of buckets.
+    TooManyBuckets(usize),
+    /// Filters have different numbers of buckets, contains the number of buckets of each filter.
+    SizeMismatch(usize, usize),
+    /// Fold factor is zero or doesn't divide the number of buckets, contains the factor.
+    InvalidFoldFactor(usize),
+    /// Output buffer is too small, contains the needed size.
+    BufferTooSmall(usize),
+    /// Input ended before a bloom filter could be decoded.
+    UnexpectedEof,
+    /// Bloom filter header is malformed.
+    InvalidHeader,
+    /// Bloom filter header specifies an unknown algorithm, contains the thrift field id.
+    UnsupportedAlgorithm(i16),
+    /// Bloom filter header specifies an unknown hash, contains the thrift field id.
+    UnsupportedHash(i16),
+    /// Bloom filter header specifies an unknown compression, contains the thrift field id.
+    UnsupportedCompression(i16),
+    /// [FilterFn::self_test](crate::FilterFn::self_test) found a backend that gives different
+    /// results than the reference implementation, contains the backend.
+    SelfTestFailed(crate::Backend),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidLength(len) => write!(
+                f,
+                "buffer length {} is not a non-zero multiple of {}",
+                len,
+                crate::BUCKET_SIZE
+            ),
+            Self::Unaligned => write!(f, "buffer is not aligned to {} bytes", crate::ALIGNMENT),
+            Self::TooManyBuckets(num_buckets) => write!(
+                f,
+                "filter can't have {} buckets, maximum is {}",
+                num_buckets,
+                crate::MAX_NUM_BUCKETS
+            ),
+            Self::SizeMismatch(a, b) => write!(
+                f,
+                "filters have different number of buckets, {} and {}",
+                a, b
+            ),
+            Self::InvalidFoldFactor(factor) => write!(
+                f,
+                "fold factor {} does not divide the number of buckets",
+                factor
+            ),
+            Self::BufferTooSmall(len) => write!(f, "buffer is too small, need {} bytes", len),
+            Self::UnexpectedEof => write!(f, "unexpected end of input"),
+            Self::InvalidHeader => write!(f, "invalid bloom filter header"),
+            Self::UnsupportedAlgorithm(id) => {
+                write!(f, "unsupported bloom filter algorithm with field id {}", id)
+            }
+            Self::UnsupportedHash(id) => {
+                write!(f, "unsupported bloom filter hash with field id {}", id)
+            }
+            Self::UnsupportedCompression(id) => {
+                write!(
+                    f,
+                    "unsupported bloom filter compression with field id {}",
+                    id
+                )
+            }
+            Self::SelfTestFailed(backend) => {
+                write!(f, "{} does not match the reference implementation", backend)
+            }
+        }
+    }
+}
+
+impl core::error::Error for Error {}
@@ -0,0 +1,111 @@
+//! XXH64 implementation, used by the parquet spec to hash values before inserting them into
+//! a bloom filter.
+
+const PRIME_1: u64 = 0x9e3779b185ebca87;
+const PRIME_2: u64 = 0xc2b2ae3d27d4eb4f;
+const PRIME_3: u64 = 0x165667b19e3779f9;
+const PRIME_4: u64 = 0x85ebca77c2b2ae63;
+const PRIME_5: u64 = 0x27d4eb2f165667c5;
+
+/// Seed the parquet spec uses for hashing values.
+pub const PARQUET_SEED: u64 = 0;
+
+/// Computes XXH64 hash of `input` with `seed`.
+pub const fn xxh64(input: &[u8], seed: u64) -> u64 {
+    let len = input.len();
+    let mut pos = 0;
+
+    let mut h = if len >= 32 {
+        let mut v1 = seed.wrapping_add(PRIME_1).wrapping_add(PRIME_2);
+        let mut v2 = seed.wrapping_add(PRIME_2);
+        let mut v3 = seed;
+        let mut v4 = seed.wrapping_sub(PRIME_1);
+
+        while pos + 32 <= len {
+            v1 = round(v1, read_u64(input, pos));
+            v2 = round(v2, read_u64(input, pos + 8));
+            v3 = round(v3, read_u64(input, pos + 16));
+            v4 = round(v4, read_u64(input, pos + 24));
+            pos += 32;
+        }
+
+        let mut h = v1
+            .rotate_left(1)
+            .wrapping_add(v2.rotate_left(7))
+            .wrapping_add(v3.rotate_left(12))
+            .wrapping_add(v4.rotate_left(18));
+        h = merge_round(h, v1);
+        h = merge_round(h, v2);
+        h = merge_round(h, v3);
+        merge_round(h, v4)
+    } else {
+        seed.wrapping_add(PRIME_5)
+    };
+
+    h = h.wrapping_add(len as u64);
+
+    while pos + 8 <= len {
+        h ^= round(0, read_u64(input, pos));
+        h = h
+            .rotate_left(27)
+            .wrapping_mul(PRIME_1)
+            .wrapping_add(PRIME_4);
+        pos += 8;
+    }
+
+    if pos + 4 <= len {
+        h ^= (read_u32(input, pos) as u64).wrapping_mul(PRIME_1);
+        h = h
+            .rotate_left(23)
+            .wrapping_mul(PRIME_2)
+            .wrapping_add(PRIME_3);
+        pos += 4;
+    }
+
+    while pos < len {
+        h ^= (input[pos] as u64).wrapping_mul(PRIME_5);
+        h = h.rotate_left(11).wrapping_mul(PRIME_1);
+        pos += 1;
+    }
+
+    h ^= h >> 33;
+    h = h.wrapping_mul(PRIME_2);
+    h ^= h >> 29;
+    h = h.wrapping_mul(PRIME_3);
+    h ^ (h >> 32)
+}
+
+/// Hashes `input` the way the parquet spec does, XXH64 with [PARQUET_SEED].
+#[inline]
+pub const fn parquet_hash(input: &[u8]) -> u64 {
+    xxh64(input, PARQUET_SEED)
+}
+
+const fn round(acc: u64, input: u64) -> u64 {
+    acc.wrapping_add(input.wrapping_mul(PRIME_2))
+        .rotate_left(31)
+        .wrapping_mul(PRIME_1)
+}
+
+const fn merge_round(acc: u64, val: u64) -> u64 {
+    (acc ^ round(0, val))
+        .wrapping_mul(PRIME_1)
+        .wrapping_add(PRIME_4)
+}
+
+const fn read_u64(input: &[u8], pos: usize) -> u64 {
+    u64::from_le_bytes([
+        input[pos],
+        input[pos + 1],
+        input[pos + 2],
+        input[pos + 3],
+        input[pos + 4],
+        input[pos + 5],
+        input[pos + 6],
+        input[pos + 7],
+    ])
+}
+
+const fn read_u32(input: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes([input[pos], input[pos + 1], input[pos + 2], input[pos + 3]])
+}
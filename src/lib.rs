@@ -1,15 +1,70 @@
-#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
 #![cfg_attr(
     all(target_family = "wasm", target_feature = "simd128"),
     feature(simd_wasm64)
 )]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
 mod arch;
+#[cfg(all(feature = "alloc", target_has_atomic = "32"))]
+mod atomic;
+mod backend;
+#[cfg(feature = "alloc")]
+mod counting;
+#[cfg(feature = "alloc")]
+mod dedup;
+mod error;
+mod hash;
+mod math;
+pub mod parquet;
+#[cfg(feature = "alloc")]
+mod partition;
+#[cfg(feature = "alloc")]
+mod rotating;
+#[cfg(feature = "alloc")]
+mod sbbf;
+#[cfg(feature = "alloc")]
+mod scalable;
+mod sizing;
+mod value;
+mod view;
+
+#[cfg(all(feature = "alloc", target_has_atomic = "32"))]
+pub use atomic::AtomicSbbf;
+pub use backend::Backend;
+#[cfg(feature = "alloc")]
+pub use counting::CountingSbbf;
+#[cfg(feature = "alloc")]
+pub use dedup::{ApproxDedup, ApproxDedupExt};
+#[cfg(feature = "futures")]
+pub use dedup::{ApproxDedupStream, ApproxDedupStreamExt};
+pub use error::Error;
+pub use hash::{parquet_hash, xxh64, PARQUET_SEED};
+#[cfg(feature = "alloc")]
+pub use rotating::RotatingSbbf;
+#[cfg(feature = "alloc")]
+pub use sbbf::Sbbf;
+#[cfg(feature = "alloc")]
+pub use scalable::ScalableSbbf;
+pub use sizing::{
+    expected_fpp, fold_factor_for, num_bits_for, num_buckets_for, num_bytes_for,
+    num_bytes_for_pow2, MAX_NUM_BYTES, MIN_NUM_BYTES,
+};
+pub use value::{FixedLenDecimal, Int96, ParquetValue};
+pub use view::{SbbfMut, SbbfRef};
 
 pub const ALIGNMENT: usize = 64;
 pub const BUCKET_SIZE: usize = 32;
+/// Maximum number of buckets a filter can have, which makes the biggest filter 128 GiB.
+///
+/// Only the upper 32 bits of a hash are used to select a bucket, same as parquet-mr and
+/// arrow-rs do, so more buckets couldn't be addressed.
+pub const MAX_NUM_BUCKETS: usize = u32::MAX as usize;
 
 /// This struct gives an interface to filter methods
+#[derive(Clone, Copy)]
 pub struct FilterFn {
     inner: &'static dyn FilterImpl,
 }
@@ -23,11 +78,30 @@ impl FilterFn {
         }
     }
 
+    /// Loads the implementation for `backend`.
+    /// Returns `None` if `backend` isn't compiled in for this target or the cpu doesn't support it.
+    pub fn with_backend(backend: Backend) -> Option<Self> {
+        arch::load_backend(backend).map(|inner| Self { inner })
+    }
+
+    /// Returns the backends the current cpu supports, from the most preferred one to the least.
+    /// [FilterFn::new] loads the first one.
+    pub fn available_backends() -> impl Iterator<Item = Backend> {
+        Backend::ALL
+            .into_iter()
+            .filter(|&backend| arch::load_backend(backend).is_some())
+    }
+
+    /// Returns the backend that is being used.
+    pub fn backend(&self) -> Backend {
+        self.inner.backend()
+    }
+
     /// Check if filter bits in `buf` contain `hash`.
     /// # Safety
     /// Caller should make sure the buffer is aligned to [ALIGNMENT] bytes.
     /// The buffer should have a size of at least `num_buckets` * [BUCKET_SIZE].
-    /// `num_buckets` has to be bigger than zero.
+    /// `num_buckets` has to be bigger than zero and at most [MAX_NUM_BUCKETS].
     #[inline(always)]
     pub unsafe fn contains(&self, buf: *const u8, num_buckets: usize, hash: u64) -> bool {
         self.inner.contains(buf, num_buckets, hash)
@@ -38,23 +112,210 @@ impl FilterFn {
     /// # Safety
     /// Caller should make sure the buffer is aligned to [ALIGNMENT] bytes.
     /// The buffer should have a size of at least `num_buckets` * [BUCKET_SIZE].
-    /// `num_buckets` has to be bigger than zero.
+    /// `num_buckets` has to be bigger than zero and at most [MAX_NUM_BUCKETS].
     #[inline(always)]
     pub unsafe fn insert(&self, buf: *mut u8, num_buckets: usize, hash: u64) -> bool {
         self.inner.insert(buf, num_buckets, hash)
     }
 
+    /// Same as [FilterFn::contains] but `buf` doesn't need to be aligned.
+    /// # Safety
+    /// The buffer should have a size of at least `num_buckets` * [BUCKET_SIZE].
+    /// `num_buckets` has to be bigger than zero and at most [MAX_NUM_BUCKETS].
+    #[inline(always)]
+    pub unsafe fn contains_unaligned(&self, buf: *const u8, num_buckets: usize, hash: u64) -> bool {
+        self.inner.contains_unaligned(buf, num_buckets, hash)
+    }
+
+    /// Same as [FilterFn::insert] but `buf` doesn't need to be aligned.
+    /// # Safety
+    /// The buffer should have a size of at least `num_buckets` * [BUCKET_SIZE].
+    /// `num_buckets` has to be bigger than zero and at most [MAX_NUM_BUCKETS].
+    #[inline(always)]
+    pub unsafe fn insert_unaligned(&self, buf: *mut u8, num_buckets: usize, hash: u64) -> bool {
+        self.inner.insert_unaligned(buf, num_buckets, hash)
+    }
+
+    /// Check if filter bits in `buf` contain each hash in `hashes` and write the results into `out`.
+    ///
+    /// Buckets are computed and prefetched ahead of time so lookups into big filters overlap
+    /// instead of waiting on memory one by one.
+    /// # Panics
+    /// Panics if `out` and `hashes` have different lengths.
+    /// # Safety
+    /// The buffer should have a size of at least `num_buckets` * [BUCKET_SIZE], it doesn't need to be aligned.
+    /// `num_buckets` has to be bigger than zero and at most [MAX_NUM_BUCKETS].
+    #[inline]
+    pub unsafe fn contains_batch(
+        &self,
+        buf: *const u8,
+        num_buckets: usize,
+        hashes: &[u64],
+        out: &mut [bool],
+    ) {
+        assert_eq!(hashes.len(), out.len());
+        self.inner.contains_batch(buf, num_buckets, hashes, out)
+    }
+
+    /// Same as [FilterFn::contains_batch] but the result for `hashes[i]` is written into
+    /// bit `i % 64` of `out[i / 64]`.
+    ///
+    /// Bits after the last hash in the last used word are set to zero, words after it aren't touched.
+    /// # Panics
+    /// Panics if `out` has less than `hashes.len().div_ceil(64)` elements.
+    /// # Safety
+    /// The buffer should have a size of at least `num_buckets` * [BUCKET_SIZE], it doesn't need to be aligned.
+    /// `num_buckets` has to be bigger than zero and at most [MAX_NUM_BUCKETS].
+    #[inline]
+    pub unsafe fn contains_batch_bitmask(
+        &self,
+        buf: *const u8,
+        num_buckets: usize,
+        hashes: &[u64],
+        out: &mut [u64],
+    ) {
+        assert!(out.len() >= hashes.len().div_ceil(64));
+        self.inner
+            .contains_batch_bitmask(buf, num_buckets, hashes, out)
+    }
+
+    /// Insert each hash in `hashes` into the filter bits inside `buf`.
+    /// Returns the number of hashes that were already in the filter bits when they were inserted,
+    /// so the result is the same as counting the `true` results of calling [FilterFn::insert] in a loop.
+    ///
+    /// Buckets are computed and prefetched ahead of time like [FilterFn::contains_batch] does.
+    /// # Safety
+    /// The buffer should have a size of at least `num_buckets` * [BUCKET_SIZE], it doesn't need to be aligned.
+    /// `num_buckets` has to be bigger than zero and at most [MAX_NUM_BUCKETS].
+    #[inline]
+    pub unsafe fn insert_batch(&self, buf: *mut u8, num_buckets: usize, hashes: &[u64]) -> usize {
+        self.inner.insert_batch(buf, num_buckets, hashes)
+    }
+
+    /// Sets the filter bits in `dst` to the union of the filter bits in `dst` and `src`.
+    ///
+    /// The result contains every hash either filter contains, same as if all hashes were
+    /// inserted into a single filter.
+    /// # Safety
+    /// Both buffers should have a size of at least `num_buckets` * [BUCKET_SIZE], they don't need to be aligned.
+    /// They can be the same buffer but they can't partially overlap.
+    #[inline]
+    pub unsafe fn union_into(&self, dst: *mut u8, src: *const u8, num_buckets: usize) {
+        self.inner.union(dst, src, num_buckets)
+    }
+
+    /// Sets the filter bits in `dst` to the intersection of the filter bits in `dst` and `src`.
+    ///
+    /// The result contains every hash both filters contain, but it can have more false
+    /// positives than a filter built from the common hashes.
+    /// # Safety
+    /// Both buffers should have a size of at least `num_buckets` * [BUCKET_SIZE], they don't need to be aligned.
+    /// They can be the same buffer but they can't partially overlap.
+    #[inline]
+    pub unsafe fn intersect_into(&self, dst: *mut u8, src: *const u8, num_buckets: usize) {
+        self.inner.intersect(dst, src, num_buckets)
+    }
+
+    /// Returns true if every bit that is set in `a` is also set in `b`, which means
+    /// `b` contains every hash `a` contains.
+    /// # Safety
+    /// Both buffers should have a size of at least `num_buckets` * [BUCKET_SIZE], they don't need to be aligned.
+    #[inline]
+    pub unsafe fn is_subset_of(&self, a: *const u8, b: *const u8, num_buckets: usize) -> bool {
+        self.inner.is_subset(a, b, num_buckets)
+    }
+
+    /// Check if any of the `num_filters` filters stored back to back in `buf` contains `hash`.
+    ///
+    /// All filters have `num_buckets` buckets so `hash` maps to the same bucket in each of them,
+    /// the bucket index and mask are computed once for all filters.
+    /// # Safety
+    /// `buf` should have a size of at least `num_filters` * `num_buckets` * [BUCKET_SIZE], it doesn't need to be aligned.
+    ///
+    /// `num_buckets` has to be bigger than zero and at most [MAX_NUM_BUCKETS].
+    #[inline]
+    pub unsafe fn contains_any(
+        &self,
+        buf: *const u8,
+        num_buckets: usize,
+        num_filters: usize,
+        hash: u64,
+    ) -> bool {
+        self.inner.contains_any(buf, num_buckets, num_filters, hash)
+    }
+
+    /// Returns the number of bits that are set in the filter.
+    /// # Safety
+    /// `buf` should have a size of at least `num_buckets` * [BUCKET_SIZE], it doesn't need to be aligned.
+    #[inline]
+    pub unsafe fn count_ones(&self, buf: *const u8, num_buckets: usize) -> u64 {
+        self.inner.count_ones(buf, num_buckets)
+    }
+
+    /// Returns the number of bits that are set in `a` or in `b`, same as counting the bits of
+    /// their union without writing it anywhere.
+    /// # Safety
+    /// Both buffers should have a size of at least `num_buckets` * [BUCKET_SIZE], they don't need to be aligned.
+    #[inline]
+    pub unsafe fn count_ones_union(&self, a: *const u8, b: *const u8, num_buckets: usize) -> u64 {
+        self.inner.count_ones_union(a, b, num_buckets)
+    }
+
     /// Returns a string indicating which internal filter implementation is being used
     pub fn which(&self) -> &'static str {
-        self.inner.which()
+        self.backend().name()
+    }
+
+    /// Runs a fixed set of hashes through the backend and the portable reference implementation
+    /// and checks that they give the same results and the same filter bytes.
+    ///
+    /// Meant to be called once at startup on hardware the binary wasn't tested on, so a miscompiled
+    /// or broken SIMD path is caught before it writes filters that other readers can't use.
+    /// Doesn't allocate any memory.
+    pub fn self_test(&self) -> Result<(), Error> {
+        if arch::self_test(self.inner) {
+            Ok(())
+        } else {
+            Err(Error::SelfTestFailed(self.backend()))
+        }
     }
 }
 
 trait FilterImpl {
     unsafe fn contains(&self, buf: *const u8, num_buckets: usize, hash: u64) -> bool;
     unsafe fn insert(&self, buf: *mut u8, num_buckets: usize, hash: u64) -> bool;
+    unsafe fn contains_unaligned(&self, buf: *const u8, num_buckets: usize, hash: u64) -> bool;
+    unsafe fn insert_unaligned(&self, buf: *mut u8, num_buckets: usize, hash: u64) -> bool;
+    unsafe fn insert_batch(&self, buf: *mut u8, num_buckets: usize, hashes: &[u64]) -> usize;
+    unsafe fn contains_batch(
+        &self,
+        buf: *const u8,
+        num_buckets: usize,
+        hashes: &[u64],
+        out: &mut [bool],
+    );
+    unsafe fn contains_batch_bitmask(
+        &self,
+        buf: *const u8,
+        num_buckets: usize,
+        hashes: &[u64],
+        out: &mut [u64],
+    );
+
+    unsafe fn union(&self, dst: *mut u8, src: *const u8, num_buckets: usize);
+    unsafe fn intersect(&self, dst: *mut u8, src: *const u8, num_buckets: usize);
+    unsafe fn is_subset(&self, a: *const u8, b: *const u8, num_buckets: usize) -> bool;
+    unsafe fn contains_any(
+        &self,
+        buf: *const u8,
+        num_buckets: usize,
+        num_filters: usize,
+        hash: u64,
+    ) -> bool;
+    unsafe fn count_ones(&self, buf: *const u8, num_buckets: usize) -> u64;
+    unsafe fn count_ones_union(&self, a: *const u8, b: *const u8, num_buckets: usize) -> u64;
 
-    fn which(&self) -> &'static str;
+    fn backend(&self) -> Backend;
 }
 
 impl Default for FilterFn {
@@ -62,3 +323,108 @@ impl Default for FilterFn {
         Self::new()
     }
 }
+
+impl core::fmt::Debug for FilterFn {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("FilterFn").field(&self.which()).finish()
+    }
+}
+
+/// Same interface as [FilterFn] but the backend is picked at compile time from the enabled
+/// target features instead of at runtime, so calls go straight to the backend and can be inlined.
+///
+/// Default x86_64 targets don't enable SSE4.1, so without flags like `-C target-cpu=native` this
+/// uses the fallback implementation there. Prefer [FilterFn] for binaries that are built once and
+/// run on different cpus.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StaticFilterFn;
+
+impl StaticFilterFn {
+    /// Returns the backend that was picked at compile time.
+    pub fn backend(&self) -> Backend {
+        arch::StaticFilter.backend()
+    }
+
+    /// Same as [FilterFn::contains].
+    /// # Safety
+    /// Caller should make sure the buffer is aligned to [ALIGNMENT] bytes.
+    /// The buffer should have a size of at least `num_buckets` * [BUCKET_SIZE].
+    /// `num_buckets` has to be bigger than zero and at most [MAX_NUM_BUCKETS].
+    #[inline(always)]
+    pub unsafe fn contains(&self, buf: *const u8, num_buckets: usize, hash: u64) -> bool {
+        arch::StaticFilter.contains(buf, num_buckets, hash)
+    }
+
+    /// Same as [FilterFn::insert].
+    /// # Safety
+    /// Caller should make sure the buffer is aligned to [ALIGNMENT] bytes.
+    /// The buffer should have a size of at least `num_buckets` * [BUCKET_SIZE].
+    /// `num_buckets` has to be bigger than zero and at most [MAX_NUM_BUCKETS].
+    #[inline(always)]
+    pub unsafe fn insert(&self, buf: *mut u8, num_buckets: usize, hash: u64) -> bool {
+        arch::StaticFilter.insert(buf, num_buckets, hash)
+    }
+
+    /// Same as [FilterFn::contains_unaligned].
+    /// # Safety
+    /// The buffer should have a size of at least `num_buckets` * [BUCKET_SIZE].
+    /// `num_buckets` has to be bigger than zero and at most [MAX_NUM_BUCKETS].
+    #[inline(always)]
+    pub unsafe fn contains_unaligned(&self, buf: *const u8, num_buckets: usize, hash: u64) -> bool {
+        arch::StaticFilter.contains_unaligned(buf, num_buckets, hash)
+    }
+
+    /// Same as [FilterFn::insert_unaligned].
+    /// # Safety
+    /// The buffer should have a size of at least `num_buckets` * [BUCKET_SIZE].
+    /// `num_buckets` has to be bigger than zero and at most [MAX_NUM_BUCKETS].
+    #[inline(always)]
+    pub unsafe fn insert_unaligned(&self, buf: *mut u8, num_buckets: usize, hash: u64) -> bool {
+        arch::StaticFilter.insert_unaligned(buf, num_buckets, hash)
+    }
+
+    /// Same as [FilterFn::contains_batch].
+    /// # Panics
+    /// Panics if `out` and `hashes` have different lengths.
+    /// # Safety
+    /// The buffer should have a size of at least `num_buckets` * [BUCKET_SIZE], it doesn't need to be aligned.
+    /// `num_buckets` has to be bigger than zero and at most [MAX_NUM_BUCKETS].
+    #[inline]
+    pub unsafe fn contains_batch(
+        &self,
+        buf: *const u8,
+        num_buckets: usize,
+        hashes: &[u64],
+        out: &mut [bool],
+    ) {
+        assert_eq!(hashes.len(), out.len());
+        arch::StaticFilter.contains_batch(buf, num_buckets, hashes, out)
+    }
+
+    /// Same as [FilterFn::contains_batch_bitmask].
+    /// # Panics
+    /// Panics if `out` has less than `hashes.len().div_ceil(64)` elements.
+    /// # Safety
+    /// The buffer should have a size of at least `num_buckets` * [BUCKET_SIZE], it doesn't need to be aligned.
+    /// `num_buckets` has to be bigger than zero and at most [MAX_NUM_BUCKETS].
+    #[inline]
+    pub unsafe fn contains_batch_bitmask(
+        &self,
+        buf: *const u8,
+        num_buckets: usize,
+        hashes: &[u64],
+        out: &mut [u64],
+    ) {
+        assert!(out.len() >= hashes.len().div_ceil(64));
+        arch::StaticFilter.contains_batch_bitmask(buf, num_buckets, hashes, out)
+    }
+
+    /// Same as [FilterFn::insert_batch].
+    /// # Safety
+    /// The buffer should have a size of at least `num_buckets` * [BUCKET_SIZE], it doesn't need to be aligned.
+    /// `num_buckets` has to be bigger than zero and at most [MAX_NUM_BUCKETS].
+    #[inline]
+    pub unsafe fn insert_batch(&self, buf: *mut u8, num_buckets: usize, hashes: &[u64]) -> usize {
+        arch::StaticFilter.insert_batch(buf, num_buckets, hashes)
+    }
+}
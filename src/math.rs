@@ -0,0 +1,140 @@
+//! `const fn` float helpers, `core` doesn't have `ln`/`exp` and we don't want to depend on `libm`.
+
+const LN_2: f64 = core::f64::consts::LN_2;
+
+/// Natural logarithm of `x`.
+/// `x` has to be a positive normal number.
+pub(crate) const fn ln(x: f64) -> f64 {
+    let bits = x.to_bits();
+    let mut e = ((bits >> 52) & 0x7ff) as i64 - 1023;
+    // mantissa in [1, 2)
+    let mut m = f64::from_bits((bits & 0x000f_ffff_ffff_ffff) | 0x3ff0_0000_0000_0000);
+    if m > core::f64::consts::SQRT_2 {
+        m /= 2.0;
+        e += 1;
+    }
+
+    // ln(m) = 2 * atanh(s), |s| < 0.172
+    let s = (m - 1.0) / (m + 1.0);
+    let s2 = s * s;
+    let mut term = s;
+    let mut sum = 0.0;
+    let mut i = 1;
+    while i < 40 {
+        sum += term / i as f64;
+        term *= s2;
+        i += 2;
+    }
+
+    e as f64 * LN_2 + 2.0 * sum
+}
+
+/// e raised to the power of `x`.
+pub(crate) const fn exp(x: f64) -> f64 {
+    if x < -745.0 {
+        return 0.0;
+    }
+    if x > 709.0 {
+        return f64::INFINITY;
+    }
+
+    let k = round(x / LN_2);
+    // |r| <= ln(2) / 2
+    let r = x - k * LN_2;
+
+    let mut term = 1.0;
+    let mut sum = 1.0;
+    let mut i = 1;
+    while i < 24 {
+        term *= r / i as f64;
+        sum += term;
+        i += 1;
+    }
+
+    sum * pow2(k as i64)
+}
+
+/// `base` raised to the power of `e`.
+/// `base` has to be a positive normal number.
+pub(crate) const fn powf(base: f64, e: f64) -> f64 {
+    exp(e * ln(base))
+}
+
+pub(crate) const fn sqrt(x: f64) -> f64 {
+    if x == 0.0 {
+        return 0.0;
+    }
+    powf(x, 0.5)
+}
+
+/// ln(n!)
+pub(crate) const fn ln_factorial(n: u64) -> f64 {
+    if n < 16 {
+        let mut sum = 0.0;
+        let mut i = 2;
+        while i <= n {
+            sum += ln(i as f64);
+            i += 1;
+        }
+        return sum;
+    }
+
+    // Stirling series
+    let n = n as f64;
+    n * ln(n) - n + 0.5 * ln(2.0 * core::f64::consts::PI * n) + 1.0 / (12.0 * n)
+        - 1.0 / (360.0 * n * n * n)
+}
+
+const fn round(x: f64) -> f64 {
+    if x >= 0.0 {
+        (x + 0.5) as i64 as f64
+    } else {
+        (x - 0.5) as i64 as f64
+    }
+}
+
+/// 2 raised to the power of `k`, handles subnormal results.
+const fn pow2(k: i64) -> f64 {
+    if k > 1023 {
+        f64::INFINITY
+    } else if k >= -1022 {
+        f64::from_bits(((k + 1023) as u64) << 52)
+    } else if k >= -1074 {
+        f64::from_bits(1 << (k + 1074))
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn assert_close(a: f64, b: f64) {
+        let err = if b == 0.0 {
+            a.abs()
+        } else {
+            ((a - b) / b).abs()
+        };
+        assert!(err < 1e-13, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn matches_std() {
+        for i in 1..10_000 {
+            let x = i as f64 * 0.0173;
+            assert_close(ln(x), x.ln());
+            assert_close(ln(1.0 / x), (1.0 / x).ln());
+            assert_close(exp(x / 20.0), (x / 20.0).exp());
+            assert_close(exp(-x / 20.0), (-x / 20.0).exp());
+            assert_close(powf(x, 1.0 / 8.0), x.powf(1.0 / 8.0));
+            assert_close(sqrt(x), x.sqrt());
+        }
+
+        for n in 0..200u64 {
+            let expected: f64 = (2..=n).map(|i| (i as f64).ln()).sum();
+            let err = (ln_factorial(n) - expected).abs();
+            assert!(err < 1e-9 * expected.max(1.0), "{}", n);
+        }
+    }
+}
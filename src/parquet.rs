@@ -0,0 +1,320 @@
+//! Encoding and decoding of parquet bloom filters, as described in the
+//! [spec](https://github.com/apache/parquet-format/blob/master/BloomFilter.md#file-format).
+//!
+//! A bloom filter is stored as a thrift compact encoded `BloomFilterHeader` followed by the
+//! filter bitset.
+
+use crate::Error;
+
+/// Maximum length of an encoded [BloomFilterHeader].
+pub const MAX_HEADER_LEN: usize = 19;
+
+// thrift compact protocol field types
+const TYPE_BOOL_TRUE: u8 = 1;
+const TYPE_BOOL_FALSE: u8 = 2;
+const TYPE_BYTE: u8 = 3;
+const TYPE_I16: u8 = 4;
+const TYPE_I32: u8 = 5;
+const TYPE_I64: u8 = 6;
+const TYPE_DOUBLE: u8 = 7;
+const TYPE_BINARY: u8 = 8;
+const TYPE_LIST: u8 = 9;
+const TYPE_SET: u8 = 10;
+const TYPE_MAP: u8 = 11;
+const TYPE_STRUCT: u8 = 12;
+
+const MAX_NESTING: usize = 64;
+
+/// Header of a parquet bloom filter.
+///
+/// Only the split block algorithm, xxhash and no compression are defined by the spec so
+/// the header only carries the size of the bitset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BloomFilterHeader {
+    /// Size of the bitset in bytes.
+    pub num_bytes: i32,
+}
+
+impl BloomFilterHeader {
+    pub fn new(num_bytes: i32) -> Self {
+        Self { num_bytes }
+    }
+
+    /// Returns the length of the encoded header.
+    pub fn encoded_len(&self) -> usize {
+        MAX_HEADER_LEN - 5 + varint_len(zigzag_i32(self.num_bytes))
+    }
+
+    /// Encodes the header into `out` and returns the number of bytes written.
+    ///
+    /// Returns an error if `out` is shorter than [BloomFilterHeader::encoded_len].
+    pub fn encode(&self, out: &mut [u8]) -> Result<usize, Error> {
+        let len = self.encoded_len();
+        if out.len() < len {
+            return Err(Error::BufferTooSmall(len));
+        }
+
+        let mut pos = 0;
+        // numBytes: field 1, i32
+        out[pos] = 1 << 4 | TYPE_I32;
+        pos += 1;
+        let mut val = zigzag_i32(self.num_bytes);
+        while val >= 0x80 {
+            out[pos] = (val as u8) | 0x80;
+            val >>= 7;
+            pos += 1;
+        }
+        out[pos] = val as u8;
+        pos += 1;
+
+        // algorithm, hash and compression: fields 2, 3 and 4.
+        // Each one is a union with an empty struct set as field 1.
+        for _ in 0..3 {
+            out[pos..pos + 4].copy_from_slice(&[1 << 4 | TYPE_STRUCT, 1 << 4 | TYPE_STRUCT, 0, 0]);
+            pos += 4;
+        }
+
+        out[pos] = 0;
+        pos += 1;
+
+        debug_assert_eq!(pos, len);
+
+        Ok(pos)
+    }
+
+    /// Decodes a header from the start of `buf`.
+    /// Returns the header and the number of bytes it occupies.
+    ///
+    /// Returns an error if the header is malformed or if it specifies an algorithm, hash or
+    /// compression that isn't supported.
+    pub fn decode(buf: &[u8]) -> Result<(Self, usize), Error> {
+        let mut reader = Reader { buf, pos: 0 };
+
+        let mut num_bytes = None;
+        let mut algorithm = false;
+        let mut hash = false;
+        let mut compression = false;
+
+        let mut last_id = 0;
+        while let Some((field_type, id)) = reader.read_field_header(&mut last_id)? {
+            match (id, field_type) {
+                (1, TYPE_I32) => num_bytes = Some(reader.read_i32()?),
+                (2, TYPE_STRUCT) => {
+                    reader.read_union(Error::UnsupportedAlgorithm)?;
+                    algorithm = true;
+                }
+                (3, TYPE_STRUCT) => {
+                    reader.read_union(Error::UnsupportedHash)?;
+                    hash = true;
+                }
+                (4, TYPE_STRUCT) => {
+                    reader.read_union(Error::UnsupportedCompression)?;
+                    compression = true;
+                }
+                (1..=4, _) => return Err(Error::InvalidHeader),
+                _ => reader.skip(field_type, 0)?,
+            }
+        }
+
+        match num_bytes {
+            Some(num_bytes) if num_bytes >= 0 && algorithm && hash && compression => {
+                Ok((Self { num_bytes }, reader.pos))
+            }
+            _ => Err(Error::InvalidHeader),
+        }
+    }
+
+    /// Writes the header into `w`.
+    #[cfg(feature = "std")]
+    pub fn write<W: std::io::Write>(&self, w: &mut W) -> std::io::Result<()> {
+        let mut buf = [0; MAX_HEADER_LEN];
+        let len = self.encode(&mut buf).unwrap();
+        w.write_all(&buf[..len])
+    }
+}
+
+/// Encodes a bloom filter with `bitset` into `out`, returns the number of bytes written.
+///
+/// Returns an error if `out` is too small or if `bitset` is too big to be described by a header.
+pub fn encode_bloom_filter(bitset: &[u8], out: &mut [u8]) -> Result<usize, Error> {
+    let header = header_for(bitset)?;
+    let header_len = header.encoded_len();
+    let len = header_len + bitset.len();
+    if out.len() < len {
+        return Err(Error::BufferTooSmall(len));
+    }
+    header.encode(out)?;
+    out[header_len..len].copy_from_slice(bitset);
+    Ok(len)
+}
+
+/// Writes a bloom filter with `bitset` into `w`.
+#[cfg(feature = "std")]
+pub fn write_bloom_filter<W: std::io::Write>(bitset: &[u8], w: &mut W) -> std::io::Result<()> {
+    let header =
+        header_for(bitset).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    header.write(w)?;
+    w.write_all(bitset)
+}
+
+/// Decodes a bloom filter from the start of `buf`.
+/// Returns the header and the bitset which is borrowed from `buf`.
+///
+/// The bitset is generally not aligned so it should be used with
+/// [SbbfRef::new_unaligned](crate::SbbfRef::new_unaligned).
+pub fn decode_bloom_filter(buf: &[u8]) -> Result<(BloomFilterHeader, &[u8]), Error> {
+    let (header, header_len) = BloomFilterHeader::decode(buf)?;
+    let bitset = buf[header_len..]
+        .get(..header.num_bytes as usize)
+        .ok_or(Error::UnexpectedEof)?;
+    Ok((header, bitset))
+}
+
+fn header_for(bitset: &[u8]) -> Result<BloomFilterHeader, Error> {
+    i32::try_from(bitset.len())
+        .map(BloomFilterHeader::new)
+        .map_err(|_| Error::InvalidLength(bitset.len()))
+}
+
+fn zigzag_i32(val: i32) -> u32 {
+    ((val << 1) ^ (val >> 31)) as u32
+}
+
+fn varint_len(mut val: u32) -> usize {
+    let mut len = 1;
+    while val >= 0x80 {
+        val >>= 7;
+        len += 1;
+    }
+    len
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn read_byte(&mut self) -> Result<u8, Error> {
+        let b = *self.buf.get(self.pos).ok_or(Error::UnexpectedEof)?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn read_varint(&mut self) -> Result<u64, Error> {
+        let mut val = 0;
+        for shift in (0..64).step_by(7) {
+            let b = self.read_byte()?;
+            val |= u64::from(b & 0x7f) << shift;
+            if b & 0x80 == 0 {
+                return Ok(val);
+            }
+        }
+        Err(Error::InvalidHeader)
+    }
+
+    fn read_zigzag(&mut self) -> Result<i64, Error> {
+        let val = self.read_varint()?;
+        Ok((val >> 1) as i64 ^ -((val & 1) as i64))
+    }
+
+    fn read_i32(&mut self) -> Result<i32, Error> {
+        i32::try_from(self.read_zigzag()?).map_err(|_| Error::InvalidHeader)
+    }
+
+    /// Returns the type and the id of the next field or None if the struct ended.
+    fn read_field_header(&mut self, last_id: &mut i16) -> Result<Option<(u8, i16)>, Error> {
+        let b = self.read_byte()?;
+        if b == 0 {
+            return Ok(None);
+        }
+        let delta = (b >> 4) as i16;
+        let id = if delta == 0 {
+            i16::try_from(self.read_zigzag()?).map_err(|_| Error::InvalidHeader)?
+        } else {
+            last_id.checked_add(delta).ok_or(Error::InvalidHeader)?
+        };
+        *last_id = id;
+        Ok(Some((b & 0x0f, id)))
+    }
+
+    /// Reads a union which has to have field 1 set to a struct.
+    fn read_union(&mut self, unsupported: fn(i16) -> Error) -> Result<(), Error> {
+        let mut last_id = 0;
+        match self.read_field_header(&mut last_id)? {
+            Some((TYPE_STRUCT, 1)) => self.skip(TYPE_STRUCT, 1)?,
+            Some((_, id)) => return Err(unsupported(id)),
+            None => return Err(Error::InvalidHeader),
+        }
+        match self.read_field_header(&mut last_id)? {
+            None => Ok(()),
+            Some(_) => Err(Error::InvalidHeader),
+        }
+    }
+
+    fn skip(&mut self, field_type: u8, depth: usize) -> Result<(), Error> {
+        if depth > MAX_NESTING {
+            return Err(Error::InvalidHeader);
+        }
+        match field_type {
+            TYPE_BOOL_TRUE | TYPE_BOOL_FALSE => {}
+            TYPE_BYTE => {
+                self.read_byte()?;
+            }
+            TYPE_I16 | TYPE_I32 | TYPE_I64 => {
+                self.read_varint()?;
+            }
+            TYPE_DOUBLE => self.skip_bytes(8)?,
+            TYPE_BINARY => {
+                let len = self.read_varint()?;
+                self.skip_bytes(len)?;
+            }
+            TYPE_LIST | TYPE_SET => {
+                let b = self.read_byte()?;
+                let len = match b >> 4 {
+                    15 => self.read_varint()?,
+                    len => u64::from(len),
+                };
+                let elem_type = b & 0x0f;
+                for _ in 0..len {
+                    // bool elements are encoded as a byte each
+                    match elem_type {
+                        TYPE_BOOL_TRUE | TYPE_BOOL_FALSE => self.skip_bytes(1)?,
+                        _ => self.skip(elem_type, depth + 1)?,
+                    }
+                }
+            }
+            TYPE_MAP => {
+                let len = self.read_varint()?;
+                if len > 0 {
+                    let types = self.read_byte()?;
+                    for _ in 0..len {
+                        for elem_type in [types >> 4, types & 0x0f] {
+                            match elem_type {
+                                TYPE_BOOL_TRUE | TYPE_BOOL_FALSE => self.skip_bytes(1)?,
+                                _ => self.skip(elem_type, depth + 1)?,
+                            }
+                        }
+                    }
+                }
+            }
+            TYPE_STRUCT => {
+                let mut last_id = 0;
+                while let Some((field_type, _)) = self.read_field_header(&mut last_id)? {
+                    self.skip(field_type, depth + 1)?;
+                }
+            }
+            _ => return Err(Error::InvalidHeader),
+        }
+        Ok(())
+    }
+
+    fn skip_bytes(&mut self, len: u64) -> Result<(), Error> {
+        let remaining = (self.buf.len() - self.pos) as u64;
+        if len > remaining {
+            return Err(Error::UnexpectedEof);
+        }
+        self.pos += len as usize;
+        Ok(())
+    }
+}
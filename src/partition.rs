@@ -0,0 +1,66 @@
+//! Radix partitioning of hashes by bucket, used to make bulk inserts into big filters
+//! mostly sequential.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::{arch, FilterFn};
+
+/// Log2 of the minimum number of buckets in a partition.
+/// 8192 buckets are 256 KiB of filter bits which fit into the L2 cache of most cpus.
+const PARTITION_SHIFT: u32 = 13;
+/// Log2 of the maximum number of partitions, keeps the counts small for huge filters.
+const MAX_PARTITIONS_SHIFT: u32 = 16;
+/// Maximum number of hashes that are partitioned at a time, limits the size of the scratch buffer.
+const MAX_SCRATCH_LEN: usize = 1 << 20;
+
+/// Inserts `hashes` into the filter bits inside `buf` after sorting them by the region of the
+/// filter they fall into. Returns the number of hashes that were already in the filter.
+///
+/// The sort is stable so the result is the same as inserting the hashes one by one.
+/// # Safety
+/// Same as [FilterFn::insert_batch].
+pub(crate) unsafe fn insert_partitioned(
+    filter_fn: FilterFn,
+    buf: *mut u8,
+    num_buckets: usize,
+    hashes: &[u64],
+) -> usize {
+    if num_buckets <= 1 << PARTITION_SHIFT || hashes.len() <= 1 << PARTITION_SHIFT {
+        return filter_fn.insert_batch(buf, num_buckets, hashes);
+    }
+
+    let num_bits = usize::BITS - (num_buckets - 1).leading_zeros();
+    let shift = PARTITION_SHIFT.max(num_bits.saturating_sub(MAX_PARTITIONS_SHIFT));
+    let num_partitions = ((num_buckets - 1) >> shift) + 1;
+
+    let mut offsets = vec![0; num_partitions];
+    let mut scratch = Vec::with_capacity(hashes.len().min(MAX_SCRATCH_LEN));
+    let mut num_present = 0;
+
+    for chunk in hashes.chunks(MAX_SCRATCH_LEN) {
+        offsets.fill(0);
+        for &hash in chunk {
+            offsets[arch::bucket_index(num_buckets, hash) >> shift] += 1;
+        }
+
+        let mut start = 0;
+        for offset in offsets.iter_mut() {
+            let count = *offset;
+            *offset = start;
+            start += count;
+        }
+
+        scratch.clear();
+        scratch.resize(chunk.len(), 0);
+        for &hash in chunk {
+            let offset = &mut offsets[arch::bucket_index(num_buckets, hash) >> shift];
+            scratch[*offset] = hash;
+            *offset += 1;
+        }
+
+        num_present += filter_fn.insert_batch(buf, num_buckets, &scratch);
+    }
+
+    num_present
+}
@@ -0,0 +1,187 @@
+use core::fmt;
+
+use crate::view::is_aligned;
+use crate::{
+    Backend, FilterFn, ParquetValue, Sbbf, SbbfMut, SbbfRef, BUCKET_SIZE, MAX_NUM_BUCKETS,
+};
+
+/// Split block bloom filter that forgets hashes after some number of rotations, for
+/// "seen recently" checks like deduplicating a stream of events over a time window.
+///
+/// The filter keeps `num_generations` filters of the same size. Hashes are inserted into the
+/// newest generation and [RotatingSbbf::rotate] drops the oldest generation and starts a new one,
+/// so calling `rotate` every `N / num_generations` minutes gives a window of at least
+/// `N - N / num_generations` minutes.
+///
+/// Generations are stored back to back in one buffer and a lookup checks the same bucket in
+/// each of them with [FilterFn::contains_any](crate::FilterFn::contains_any), so the bucket and
+/// mask of a hash are computed once for all generations.
+pub struct RotatingSbbf {
+    /// Generations stored back to back, it isn't used as a filter by itself.
+    storage: Sbbf,
+    num_buckets: usize,
+    num_generations: usize,
+    /// Index of the newest generation in `storage`.
+    newest: usize,
+}
+
+impl RotatingSbbf {
+    /// Allocates a filter with `num_generations` empty generations of `num_buckets` buckets.
+    /// # Panics
+    /// Panics if `num_buckets` or `num_generations` is zero, or if the total number of buckets
+    /// of all generations is bigger than [MAX_NUM_BUCKETS].
+    pub fn new(num_buckets: usize, num_generations: usize) -> Self {
+        Self::with_storage(num_buckets, num_generations, Sbbf::new)
+    }
+
+    /// Same as [RotatingSbbf::new] but uses the implementation for `backend`, see
+    /// [FilterFn::with_backend](crate::FilterFn::with_backend).
+    /// Returns `None` if `backend` isn't available on this cpu.
+    /// # Panics
+    /// Panics if `num_buckets` or `num_generations` is zero, or if the total number of buckets
+    /// of all generations is bigger than [MAX_NUM_BUCKETS].
+    pub fn with_backend(
+        num_buckets: usize,
+        num_generations: usize,
+        backend: Backend,
+    ) -> Option<Self> {
+        FilterFn::with_backend(backend)?;
+        Some(Self::with_storage(num_buckets, num_generations, |total| {
+            Sbbf::with_backend(total, backend).unwrap()
+        }))
+    }
+
+    fn with_storage(
+        num_buckets: usize,
+        num_generations: usize,
+        storage: impl FnOnce(usize) -> Sbbf,
+    ) -> Self {
+        assert!(num_buckets > 0, "num_buckets has to be bigger than zero");
+        assert!(
+            num_generations > 0,
+            "num_generations has to be bigger than zero"
+        );
+        let total = num_buckets
+            .checked_mul(num_generations)
+            .filter(|&total| total <= MAX_NUM_BUCKETS)
+            .expect("total number of buckets can't be bigger than MAX_NUM_BUCKETS");
+
+        Self {
+            storage: storage(total),
+            num_buckets,
+            num_generations,
+            newest: 0,
+        }
+    }
+
+    /// Check if any generation contains `hash`.
+    #[inline]
+    pub fn contains(&self, hash: u64) -> bool {
+        self.contains_in(0..self.num_generations, hash)
+    }
+
+    /// Insert `hash` into the newest generation.
+    /// Returns true if no generation contained `hash` before.
+    ///
+    /// A hash that was already in an older generation is still inserted, so it stays in the
+    /// filter for the full window after the last time it was seen.
+    #[inline]
+    pub fn insert_if_new(&mut self, hash: u64) -> bool {
+        if self.generation_mut(self.newest).insert(hash) {
+            return false;
+        }
+        // newest generation didn't have it, check the others
+        !(self.contains_in(0..self.newest, hash)
+            || self.contains_in(self.newest + 1..self.num_generations, hash))
+    }
+
+    /// Check if any generation contains `value`, hashed by its parquet plain encoding.
+    #[inline]
+    pub fn contains_value<T: ParquetValue + ?Sized>(&self, value: &T) -> bool {
+        self.contains(value.parquet_hash())
+    }
+
+    /// Insert `value` into the newest generation, hashed by its parquet plain encoding.
+    /// Returns true if no generation contained `value` before. See [RotatingSbbf::insert_if_new].
+    #[inline]
+    pub fn insert_value_if_new<T: ParquetValue + ?Sized>(&mut self, value: &T) -> bool {
+        self.insert_if_new(value.parquet_hash())
+    }
+
+    /// Drops the oldest generation and starts a new empty one.
+    pub fn rotate(&mut self) {
+        self.newest = (self.newest + 1) % self.num_generations;
+        let range = self.byte_range(self.newest);
+        self.storage.as_bytes_mut()[range].fill(0);
+    }
+
+    /// Returns a view of the generation that was started `age` rotations ago,
+    /// 0 is the newest generation.
+    /// # Panics
+    /// Panics if `age` isn't smaller than the number of generations.
+    pub fn generation(&self, age: usize) -> SbbfRef<'_> {
+        assert!(
+            age < self.num_generations,
+            "age has to be smaller than num_generations"
+        );
+        let idx = (self.newest + self.num_generations - age) % self.num_generations;
+        let buf = &self.storage.as_bytes()[self.byte_range(idx)];
+        SbbfRef {
+            filter_fn: self.storage.view().filter_fn,
+            buf,
+            aligned: is_aligned(buf),
+        }
+    }
+
+    /// Returns the number of buckets in each generation.
+    pub fn num_buckets(&self) -> usize {
+        self.num_buckets
+    }
+
+    /// Returns the number of generations.
+    pub fn num_generations(&self) -> usize {
+        self.num_generations
+    }
+
+    /// Checks the generations in `range` of `storage`.
+    fn contains_in(&self, range: core::ops::Range<usize>, hash: u64) -> bool {
+        if range.is_empty() {
+            return false;
+        }
+        let view = self.storage.view();
+        unsafe {
+            view.filter_fn.contains_any(
+                view.buf[self.byte_range(range.start).start..].as_ptr(),
+                self.num_buckets,
+                range.len(),
+                hash,
+            )
+        }
+    }
+
+    fn generation_mut(&mut self, idx: usize) -> SbbfMut<'_> {
+        let range = self.byte_range(idx);
+        let filter_fn = self.storage.view().filter_fn;
+        let buf = &mut self.storage.as_bytes_mut()[range];
+        let aligned = is_aligned(buf);
+        SbbfMut {
+            filter_fn,
+            buf,
+            aligned,
+        }
+    }
+
+    fn byte_range(&self, idx: usize) -> core::ops::Range<usize> {
+        let len = self.num_buckets * BUCKET_SIZE;
+        idx * len..(idx + 1) * len
+    }
+}
+
+impl fmt::Debug for RotatingSbbf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RotatingSbbf")
+            .field("num_buckets", &self.num_buckets)
+            .field("num_generations", &self.num_generations)
+            .finish()
+    }
+}
@@ -0,0 +1,274 @@
+use alloc::alloc::{alloc_zeroed, dealloc, handle_alloc_error, Layout};
+use core::fmt;
+use core::ptr::NonNull;
+
+use crate::view::{check_fold_factor, fold_buckets};
+use crate::{
+    num_buckets_for, Backend, Error, FilterFn, ParquetValue, SbbfMut, SbbfRef, ALIGNMENT,
+    BUCKET_SIZE, MAX_NUM_BUCKETS,
+};
+
+/// Split block bloom filter that owns its bits.
+///
+/// The bits are stored in a zeroed buffer aligned to [ALIGNMENT] bytes so it can be
+/// passed to the [FilterFn] methods safely.
+pub struct Sbbf {
+    filter_fn: FilterFn,
+    ptr: NonNull<u8>,
+    num_buckets: usize,
+}
+
+// Sbbf owns its buffer exclusively, same as a Box<[u8]> would.
+unsafe impl Send for Sbbf {}
+unsafe impl Sync for Sbbf {}
+
+impl Sbbf {
+    /// Allocates an empty filter with `num_buckets` buckets.
+    /// # Panics
+    /// Panics if `num_buckets` is zero, bigger than [MAX_NUM_BUCKETS] or the buffer size overflows.
+    pub fn new(num_buckets: usize) -> Self {
+        assert!(num_buckets > 0, "num_buckets has to be bigger than zero");
+        assert!(
+            num_buckets <= MAX_NUM_BUCKETS,
+            "num_buckets can't be bigger than MAX_NUM_BUCKETS"
+        );
+
+        let layout = Self::layout(num_buckets);
+        let ptr = unsafe { alloc_zeroed(layout) };
+        let ptr = match NonNull::new(ptr) {
+            Some(ptr) => ptr,
+            None => handle_alloc_error(layout),
+        };
+
+        Self {
+            filter_fn: FilterFn::new(),
+            ptr,
+            num_buckets,
+        }
+    }
+
+    /// Same as [Sbbf::new] but uses the implementation for `backend`, see [FilterFn::with_backend].
+    /// Returns `None` if `backend` isn't available on this cpu.
+    /// # Panics
+    /// Panics if `num_buckets` is zero, bigger than [MAX_NUM_BUCKETS] or the buffer size overflows.
+    pub fn with_backend(num_buckets: usize, backend: Backend) -> Option<Self> {
+        let filter_fn = FilterFn::with_backend(backend)?;
+        let mut filter = Self::new(num_buckets);
+        filter.filter_fn = filter_fn;
+        Some(filter)
+    }
+
+    /// Allocates an empty filter sized for `ndv` distinct values and target `fpp`.
+    /// See [num_buckets_for], size is clamped to what parquet allows.
+    /// # Panics
+    /// Panics if `fpp` isn't in (0, 1).
+    pub fn for_ndv(ndv: u64, fpp: f64) -> Self {
+        Self::new(num_buckets_for(ndv, fpp))
+    }
+
+    /// Check if filter contains `hash`.
+    #[inline(always)]
+    pub fn contains(&self, hash: u64) -> bool {
+        unsafe {
+            self.filter_fn
+                .contains(self.ptr.as_ptr(), self.num_buckets, hash)
+        }
+    }
+
+    /// Insert `hash` into the filter.
+    /// Returns true if `hash` was already in the filter.
+    #[inline(always)]
+    pub fn insert(&mut self, hash: u64) -> bool {
+        unsafe {
+            self.filter_fn
+                .insert(self.ptr.as_ptr(), self.num_buckets, hash)
+        }
+    }
+
+    /// Check if filter contains `value`, hashed by its parquet plain encoding.
+    #[inline]
+    pub fn contains_value<T: ParquetValue + ?Sized>(&self, value: &T) -> bool {
+        self.contains(value.parquet_hash())
+    }
+
+    /// Insert `value` into the filter, hashed by its parquet plain encoding.
+    /// Returns true if `value` was already in the filter.
+    #[inline]
+    pub fn insert_value<T: ParquetValue + ?Sized>(&mut self, value: &T) -> bool {
+        self.insert(value.parquet_hash())
+    }
+
+    /// Insert each hash in `hashes` into the filter.
+    /// Returns the number of hashes that were already in the filter.
+    /// See [SbbfMut::insert_many].
+    pub fn insert_many(&mut self, hashes: &[u64]) -> usize {
+        self.view_mut().insert_many(hashes)
+    }
+
+    /// Check if filter contains each hash in `hashes` and write the results into `out`.
+    /// See [SbbfRef::contains_many].
+    #[inline]
+    pub fn contains_many(&self, hashes: &[u64], out: &mut [bool]) {
+        self.view().contains_many(hashes, out)
+    }
+
+    /// Same as [Sbbf::contains_many] but writes the results as a bitmask.
+    /// See [SbbfRef::contains_many_bitmask].
+    #[inline]
+    pub fn contains_many_bitmask(&self, hashes: &[u64], out: &mut [u64]) {
+        self.view().contains_many_bitmask(hashes, out)
+    }
+
+    /// Adds every hash `other` contains into this filter.
+    /// See [SbbfMut::union].
+    pub fn union(&mut self, other: SbbfRef<'_>) -> Result<(), Error> {
+        self.view_mut().union(other)
+    }
+
+    /// Clears the bits that aren't set in `other`.
+    /// See [SbbfMut::intersect].
+    pub fn intersect(&mut self, other: SbbfRef<'_>) -> Result<(), Error> {
+        self.view_mut().intersect(other)
+    }
+
+    /// Returns true if `other` contains every hash this filter contains.
+    /// See [SbbfRef::is_subset_of].
+    pub fn is_subset_of(&self, other: SbbfRef<'_>) -> Result<bool, Error> {
+        self.view().is_subset_of(other)
+    }
+
+    /// Returns a copy of the filter shrunk by `factor`, by OR-ing each group of `factor`
+    /// adjacent buckets together.
+    ///
+    /// The folded filter contains every hash this filter contains and it is the same as a filter
+    /// built with `num_buckets / factor` buckets from the same hashes, so a filter that turned out
+    /// to be too big can be shrunk without re-hashing.
+    /// [fold_factor_for](crate::fold_factor_for) can be used to pick the factor.
+    ///
+    /// Returns an error if `factor` is zero or doesn't divide the number of buckets.
+    pub fn fold(&self, factor: usize) -> Result<Sbbf, Error> {
+        let num_buckets = check_fold_factor(self.num_buckets, factor)?;
+        let filter = Self::new(num_buckets);
+        unsafe { fold_buckets(self.ptr.as_ptr(), filter.ptr.as_ptr(), num_buckets, factor) };
+        Ok(filter)
+    }
+
+    /// Returns the number of bits that are set in the filter.
+    pub fn count_ones(&self) -> u64 {
+        self.view().count_ones()
+    }
+
+    /// Returns the fraction of filter bits that are set.
+    pub fn fill_ratio(&self) -> f64 {
+        self.view().fill_ratio()
+    }
+
+    /// Estimates the number of distinct values that were inserted into the filter.
+    /// See [SbbfRef::estimate_ndv].
+    pub fn estimate_ndv(&self) -> u64 {
+        self.view().estimate_ndv()
+    }
+
+    /// Returns the false positive probability of the filter, computed from the bits that are set.
+    /// See [SbbfRef::estimated_fpp].
+    pub fn estimated_fpp(&self) -> f64 {
+        self.view().estimated_fpp()
+    }
+
+    /// Estimates the number of distinct values that were inserted into this filter or `other`.
+    /// See [SbbfRef::estimate_union_ndv].
+    pub fn estimate_union_ndv(&self, other: SbbfRef<'_>) -> Result<u64, Error> {
+        self.view().estimate_union_ndv(other)
+    }
+
+    /// Estimates the number of distinct values that were inserted into both this filter
+    /// and `other`.
+    /// See [SbbfRef::estimate_intersection_ndv].
+    pub fn estimate_intersection_ndv(&self, other: SbbfRef<'_>) -> Result<u64, Error> {
+        self.view().estimate_intersection_ndv(other)
+    }
+
+    /// Estimates the jaccard similarity of the values inserted into this filter and `other`.
+    /// See [SbbfRef::estimate_jaccard].
+    pub fn estimate_jaccard(&self, other: SbbfRef<'_>) -> Result<f64, Error> {
+        self.view().estimate_jaccard(other)
+    }
+
+    /// Returns the filter bits.
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self.ptr.as_ptr(), self.num_bytes()) }
+    }
+
+    /// Returns the filter bits as mutable.
+    /// Any bit pattern is a valid filter so this is safe to write into.
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.num_bytes()) }
+    }
+
+    /// Returns a read only view of the filter.
+    pub fn view(&self) -> SbbfRef<'_> {
+        SbbfRef {
+            filter_fn: self.filter_fn,
+            buf: self.as_bytes(),
+            aligned: true,
+        }
+    }
+
+    /// Returns a mutable view of the filter.
+    pub fn view_mut(&mut self) -> SbbfMut<'_> {
+        SbbfMut {
+            filter_fn: self.filter_fn,
+            buf: self.as_bytes_mut(),
+            aligned: true,
+        }
+    }
+
+    /// Returns the number of buckets in the filter.
+    pub fn num_buckets(&self) -> usize {
+        self.num_buckets
+    }
+
+    fn num_bytes(&self) -> usize {
+        self.num_buckets * BUCKET_SIZE
+    }
+
+    fn layout(num_buckets: usize) -> Layout {
+        let len = num_buckets
+            .checked_mul(BUCKET_SIZE)
+            .expect("filter size overflows usize");
+        Layout::from_size_align(len, ALIGNMENT).unwrap()
+    }
+}
+
+impl Drop for Sbbf {
+    fn drop(&mut self) {
+        unsafe {
+            dealloc(self.ptr.as_ptr(), Self::layout(self.num_buckets));
+        }
+    }
+}
+
+impl Clone for Sbbf {
+    fn clone(&self) -> Self {
+        let mut filter = Self::new(self.num_buckets);
+        filter.as_bytes_mut().copy_from_slice(self.as_bytes());
+        filter
+    }
+}
+
+impl PartialEq for Sbbf {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_bytes() == other.as_bytes()
+    }
+}
+
+impl Eq for Sbbf {}
+
+impl fmt::Debug for Sbbf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sbbf")
+            .field("num_buckets", &self.num_buckets)
+            .field("filter_fn", &self.filter_fn)
+            .finish()
+    }
+}
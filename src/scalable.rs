@@ -0,0 +1,166 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::sizing::fill_ratio_for;
+use crate::{expected_fpp, num_bits_for, ParquetValue, Sbbf, BUCKET_SIZE, MAX_NUM_BUCKETS};
+
+/// Each stage is planned for this many times the values of the stage before it.
+const GROWTH: u64 = 2;
+/// Each stage has this many times the false positive probability of the stage before it.
+const TIGHTENING: f64 = 0.5;
+
+/// Split block bloom filter that grows as values are inserted, for when the number of distinct
+/// values isn't known up front.
+///
+/// The filter is a chain of [Sbbf] stages. Each stage is planned for twice as many values as the
+/// one before it, with half of its false positive probability, so the false positive
+/// probabilities of the stages add up to at most the target of the whole filter.
+/// Hashes are inserted into the last stage and a new stage is started once the fraction of bits
+/// set in the last stage passes what it was planned to have at its capacity.
+///
+/// `contains` has to check every stage, so lookups get slower as the filter grows.
+/// [ScalableSbbf::compact] can be used to merge the stages if the inserted hashes are known.
+pub struct ScalableSbbf {
+    stages: Vec<Sbbf>,
+    initial_ndv: u64,
+    fpp: f64,
+    /// Fill ratio the last stage can reach before a new stage is started.
+    max_fill: f64,
+    /// Number of hashes that can be inserted into the last stage before its fill has to be
+    /// checked again.
+    unchecked: u64,
+    next_ndv: u64,
+    next_fpp: f64,
+}
+
+impl ScalableSbbf {
+    /// Creates a filter with a single stage planned for `initial_ndv` distinct values, that keeps
+    /// the false positive probability at or below `fpp` as it grows.
+    /// # Panics
+    /// Panics if `fpp` isn't in (0, 1).
+    pub fn new(initial_ndv: u64, fpp: f64) -> Self {
+        assert!(fpp > 0.0 && fpp < 1.0, "fpp has to be in (0, 1)");
+        let mut filter = Self {
+            stages: Vec::new(),
+            initial_ndv: initial_ndv.max(1),
+            fpp,
+            max_fill: 0.0,
+            unchecked: 0,
+            next_ndv: 0,
+            next_fpp: 0.0,
+        };
+        filter.push_stage(filter.initial_ndv, fpp * (1.0 - TIGHTENING));
+        filter
+    }
+
+    /// Check if filter contains `hash`.
+    #[inline]
+    pub fn contains(&self, hash: u64) -> bool {
+        // later stages are bigger so they are more likely to have the hash
+        self.stages.iter().rev().any(|stage| stage.contains(hash))
+    }
+
+    /// Insert `hash` into the filter.
+    /// Returns true if `hash` was already in the filter, in which case the filter isn't changed.
+    pub fn insert(&mut self, hash: u64) -> bool {
+        if self.contains(hash) {
+            return true;
+        }
+        self.last_stage().insert(hash);
+
+        self.unchecked -= 1;
+        if self.unchecked == 0 {
+            let fill = self.last_stage().fill_ratio();
+            if fill >= self.max_fill {
+                self.push_stage(self.next_ndv, self.next_fpp);
+            } else {
+                self.unchecked = self.unchecked_for(fill);
+            }
+        }
+        false
+    }
+
+    /// Check if filter contains `value`, hashed by its parquet plain encoding.
+    #[inline]
+    pub fn contains_value<T: ParquetValue + ?Sized>(&self, value: &T) -> bool {
+        self.contains(value.parquet_hash())
+    }
+
+    /// Insert `value` into the filter, hashed by its parquet plain encoding.
+    /// Returns true if `value` was already in the filter.
+    #[inline]
+    pub fn insert_value<T: ParquetValue + ?Sized>(&mut self, value: &T) -> bool {
+        self.insert(value.parquet_hash())
+    }
+
+    /// Replaces the stages with a single stage that has `hashes` inserted into it.
+    ///
+    /// The stage is planned for the number of hashes, so a filter that grew to many stages
+    /// answers with one lookup again.
+    /// `hashes` should have every hash that was inserted into the filter, the filter won't
+    /// contain the ones that are left out.
+    pub fn compact(&mut self, hashes: &[u64]) {
+        self.stages.clear();
+        let ndv = (hashes.len() as u64).max(self.initial_ndv);
+        self.push_stage(ndv, self.fpp * (1.0 - TIGHTENING));
+
+        let stage = self.last_stage();
+        stage.insert_many(hashes);
+        let fill = stage.fill_ratio();
+        self.unchecked = self.unchecked_for(fill);
+    }
+
+    /// Returns the stages of the filter, from the oldest to the newest.
+    pub fn stages(&self) -> &[Sbbf] {
+        &self.stages
+    }
+
+    /// Returns the false positive probability of the filter, computed from the bits that are set
+    /// in each stage. See [SbbfRef::estimated_fpp](crate::SbbfRef::estimated_fpp).
+    pub fn estimated_fpp(&self) -> f64 {
+        1.0 - self
+            .stages
+            .iter()
+            .map(|stage| 1.0 - stage.estimated_fpp())
+            .product::<f64>()
+    }
+
+    fn push_stage(&mut self, ndv: u64, fpp: f64) {
+        let mut num_buckets = num_bits_for(ndv, fpp)
+            .div_ceil(BUCKET_SIZE as u64 * 8)
+            .clamp(1, MAX_NUM_BUCKETS as u64) as usize;
+        // num_bits_for assumes the values are spread evenly over the buckets, which underestimates
+        // the false positive probability, grow the stage until the poisson model agrees.
+        while num_buckets < MAX_NUM_BUCKETS && expected_fpp(num_buckets, ndv) > fpp {
+            num_buckets = (num_buckets + num_buckets / 16 + 1).min(MAX_NUM_BUCKETS);
+        }
+        self.stages.push(Sbbf::new(num_buckets));
+        self.max_fill = fill_ratio_for(num_buckets, ndv);
+        self.unchecked = self.unchecked_for(0.0);
+        self.next_ndv = ndv.saturating_mul(GROWTH);
+        self.next_fpp = fpp * TIGHTENING;
+    }
+
+    fn last_stage(&mut self) -> &mut Sbbf {
+        self.stages.last_mut().unwrap()
+    }
+
+    /// Returns the number of hashes that can be inserted into the last stage before it can reach
+    /// `max_fill`, when it has `fill` of its bits set.
+    fn unchecked_for(&self, fill: f64) -> u64 {
+        // an insert sets at most one bit in each of the 8 words of a bucket,
+        // so it increases the fill ratio by at most 1 / (32 * num_buckets)
+        let num_buckets = self.stages.last().unwrap().num_buckets();
+        let headroom = (self.max_fill - fill) * 32.0 * num_buckets as f64;
+        (headroom as u64).max(1)
+    }
+}
+
+impl fmt::Debug for ScalableSbbf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScalableSbbf")
+            .field("num_stages", &self.stages.len())
+            .field("fpp", &self.fpp)
+            .finish()
+    }
+}
@@ -0,0 +1,145 @@
+//! Helpers for picking a filter size, based on the number of distinct values (NDV) that
+//! will be inserted and the target false positive probability (FPP).
+
+use crate::{math, BUCKET_SIZE};
+
+/// Minimum filter size in bytes allowed by the parquet spec.
+pub const MIN_NUM_BYTES: usize = 32;
+/// Maximum filter size in bytes allowed by the parquet spec.
+pub const MAX_NUM_BYTES: usize = 128 * 1024 * 1024;
+
+/// Returns the optimal number of bits for `ndv` distinct values and target `fpp`,
+/// same as `optimalNumOfBits` in parquet-mr but without any rounding or clamping.
+/// # Panics
+/// Panics if `fpp` isn't in (0, 1).
+pub const fn num_bits_for(ndv: u64, fpp: f64) -> u64 {
+    assert!(fpp > 0.0 && fpp < 1.0, "fpp has to be in (0, 1)");
+    let bits = -8.0 * ndv as f64 / math::ln(1.0 - math::powf(fpp, 1.0 / 8.0));
+    bits as u64
+}
+
+/// Returns the number of bytes a filter needs for `ndv` distinct values and target `fpp`.
+/// Result is rounded up to a multiple of [BUCKET_SIZE] and clamped to
+/// [MIN_NUM_BYTES]..=[MAX_NUM_BYTES].
+/// # Panics
+/// Panics if `fpp` isn't in (0, 1).
+pub const fn num_bytes_for(ndv: u64, fpp: f64) -> usize {
+    let bytes = num_bits_for(ndv, fpp).div_ceil(8);
+    if bytes >= MAX_NUM_BYTES as u64 {
+        return MAX_NUM_BYTES;
+    }
+    let bytes = (bytes as usize).next_multiple_of(BUCKET_SIZE);
+    if bytes < MIN_NUM_BYTES {
+        MIN_NUM_BYTES
+    } else {
+        bytes
+    }
+}
+
+/// Same as [num_bytes_for] but rounds the result up to a power of two, like parquet-mr and arrow-rs do.
+/// # Panics
+/// Panics if `fpp` isn't in (0, 1).
+pub const fn num_bytes_for_pow2(ndv: u64, fpp: f64) -> usize {
+    num_bytes_for(ndv, fpp).next_power_of_two()
+}
+
+/// Returns the number of buckets a filter needs for `ndv` distinct values and target `fpp`.
+/// See [num_bytes_for].
+/// # Panics
+/// Panics if `fpp` isn't in (0, 1).
+pub const fn num_buckets_for(ndv: u64, fpp: f64) -> usize {
+    num_bytes_for(ndv, fpp) / BUCKET_SIZE
+}
+
+/// Returns the expected false positive probability of a filter with `num_buckets` buckets
+/// after inserting `ndv` distinct values.
+///
+/// Uses the split block formula, number of values that land in a bucket follows a poisson
+/// distribution and a bucket with `i` values has a false positive probability of
+/// `(1 - (31/32)^i)^8`.
+/// # Panics
+/// Panics if `num_buckets` is zero.
+pub const fn expected_fpp(num_buckets: usize, ndv: u64) -> f64 {
+    assert!(num_buckets > 0, "num_buckets has to be bigger than zero");
+    if ndv == 0 {
+        return 0.0;
+    }
+
+    // average number of values per bucket
+    let lambda = ndv as f64 / num_buckets as f64;
+    let ln_miss = math::ln(31.0 / 32.0);
+    if lambda > 4096.0 {
+        // poisson distribution is too narrow to matter, all buckets are practically full.
+        let word_fpp = 1.0 - math::exp(lambda * ln_miss);
+        let bucket_fpp = word_fpp * word_fpp * word_fpp * word_fpp;
+        return bucket_fpp * bucket_fpp;
+    }
+
+    let ln_lambda = math::ln(lambda);
+    let spread = 12.0 * math::sqrt(lambda) + 20.0;
+
+    let start = if lambda > spread {
+        (lambda - spread) as u64
+    } else {
+        0
+    };
+    let end = (lambda + spread) as u64;
+
+    let mut fpp = 0.0;
+    let mut i = start;
+    while i <= end {
+        let ln_pmf = -lambda + i as f64 * ln_lambda - math::ln_factorial(i);
+        let word_fpp = 1.0 - math::exp(i as f64 * ln_miss);
+        let bucket_fpp = word_fpp * word_fpp * word_fpp * word_fpp;
+        fpp += math::exp(ln_pmf) * bucket_fpp * bucket_fpp;
+        i += 1;
+    }
+
+    if fpp > 1.0 {
+        1.0
+    } else {
+        fpp
+    }
+}
+
+/// Returns the estimated number of distinct values inserted into a filter with `num_buckets`
+/// buckets, that has `fill_ratio` of its bits set.
+///
+/// Inverts the split block fill model, each value sets one of the 32 bits in each word of its
+/// bucket so with a poisson distributed number of values per bucket a bit stays unset with
+/// probability `exp(-lambda / 32)`.
+/// Returns infinity if every bit is set.
+pub(crate) const fn ndv_for_fill_ratio(num_buckets: usize, fill_ratio: f64) -> f64 {
+    if fill_ratio >= 1.0 {
+        return f64::INFINITY;
+    }
+    let lambda = -32.0 * math::ln(1.0 - fill_ratio);
+    lambda * num_buckets as f64
+}
+
+/// Returns the expected fraction of bits that are set in a filter with `num_buckets` buckets
+/// after inserting `ndv` distinct values. Inverse of [ndv_for_fill_ratio].
+#[cfg(feature = "alloc")]
+pub(crate) const fn fill_ratio_for(num_buckets: usize, ndv: u64) -> f64 {
+    1.0 - math::exp(-(ndv as f64) / (32.0 * num_buckets as f64))
+}
+
+/// Returns the biggest power of two factor a filter with `num_buckets` buckets can be folded by,
+/// while keeping the [expected_fpp] for `ndv` distinct values at or below `fpp`.
+/// Returns 1 if the filter can't be folded.
+///
+/// Useful for filters that were sized for more values than they ended up getting,
+/// see [Sbbf::fold](crate::Sbbf::fold).
+/// # Panics
+/// Panics if `num_buckets` is zero or if `fpp` isn't in (0, 1).
+pub const fn fold_factor_for(num_buckets: usize, ndv: u64, fpp: f64) -> usize {
+    assert!(num_buckets > 0, "num_buckets has to be bigger than zero");
+    assert!(fpp > 0.0 && fpp < 1.0, "fpp has to be in (0, 1)");
+    let mut factor = 1;
+    while (num_buckets / factor).is_multiple_of(2)
+        && expected_fpp(num_buckets / factor / 2, ndv) <= fpp
+    {
+        factor *= 2;
+    }
+    factor
+}
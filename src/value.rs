@@ -0,0 +1,134 @@
+//! Hashing of values by their parquet plain encoding, as required by the
+//! [spec](https://github.com/apache/parquet-format/blob/master/BloomFilter.md#technical-approach).
+
+use crate::parquet_hash;
+
+/// A value that can be stored in a parquet column.
+///
+/// [ParquetValue::parquet_hash] hashes the plain encoding of the value the same way
+/// parquet-mr and arrow-rs do, so filters built with it can be probed by them and vice versa.
+///
+/// Types map to parquet physical types like this:
+/// - `bool`: BOOLEAN, encoded as a single byte like arrow-rs does. parquet-mr doesn't write
+///   bloom filters for BOOLEAN columns.
+/// - `i8`, `i16`, `i32`, `u8`, `u16`, `u32`: INT32, sign or zero extended to 32 bits.
+///   DATE, TIME(MILLIS) and DECIMAL stored as INT32 should be hashed as `i32`.
+/// - `i64`, `u64`: INT64. TIMESTAMP, TIME(MICROS/NANOS) and DECIMAL stored as INT64 should be
+///   hashed as `i64`.
+/// - [Int96] for INT96.
+/// - `f32`: FLOAT, `f64`: DOUBLE. Raw bits are hashed so `0.0` and `-0.0` are different values
+///   and NaN values are only equal if their bits are equal.
+/// - `[u8]`, `str` and their owned versions: BYTE_ARRAY. Length prefix of the plain
+///   encoding isn't hashed.
+/// - `[u8; N]`: FIXED_LEN_BYTE_ARRAY. [FixedLenDecimal] for DECIMAL stored in
+///   FIXED_LEN_BYTE_ARRAY.
+pub trait ParquetValue {
+    /// Returns XXH64 hash of the plain encoding of this value.
+    fn parquet_hash(&self) -> u64;
+}
+
+/// INT96 value, three little endian 32 bit words.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Int96(pub [u32; 3]);
+
+impl ParquetValue for Int96 {
+    #[inline]
+    fn parquet_hash(&self) -> u64 {
+        let mut bytes = [0; 12];
+        for (chunk, word) in bytes.chunks_exact_mut(4).zip(self.0) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        parquet_hash(&bytes)
+    }
+}
+
+/// DECIMAL value stored in a FIXED_LEN_BYTE_ARRAY of length `N`.
+///
+/// Encoded as big endian two's complement, `N` has to be at most 16.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FixedLenDecimal<const N: usize>(pub i128);
+
+impl<const N: usize> ParquetValue for FixedLenDecimal<N> {
+    #[inline]
+    fn parquet_hash(&self) -> u64 {
+        const { assert!(N <= 16, "decimal length can't be bigger than 16") };
+        parquet_hash(&self.0.to_be_bytes()[16 - N..])
+    }
+}
+
+macro_rules! int32 {
+    ($($t:ty),*) => {
+        $(impl ParquetValue for $t {
+            #[inline]
+            fn parquet_hash(&self) -> u64 {
+                parquet_hash(&(*self as i32).to_le_bytes())
+            }
+        })*
+    };
+}
+
+int32!(i8, i16, i32, u8, u16, u32);
+
+macro_rules! native {
+    ($($t:ty),*) => {
+        $(impl ParquetValue for $t {
+            #[inline]
+            fn parquet_hash(&self) -> u64 {
+                parquet_hash(&self.to_le_bytes())
+            }
+        })*
+    };
+}
+
+native!(i64, u64, f32, f64);
+
+impl ParquetValue for bool {
+    #[inline]
+    fn parquet_hash(&self) -> u64 {
+        parquet_hash(&[*self as u8])
+    }
+}
+
+impl ParquetValue for [u8] {
+    #[inline]
+    fn parquet_hash(&self) -> u64 {
+        parquet_hash(self)
+    }
+}
+
+impl<const N: usize> ParquetValue for [u8; N] {
+    #[inline]
+    fn parquet_hash(&self) -> u64 {
+        parquet_hash(self)
+    }
+}
+
+impl ParquetValue for str {
+    #[inline]
+    fn parquet_hash(&self) -> u64 {
+        parquet_hash(self.as_bytes())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl ParquetValue for alloc::vec::Vec<u8> {
+    #[inline]
+    fn parquet_hash(&self) -> u64 {
+        parquet_hash(self)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl ParquetValue for alloc::string::String {
+    #[inline]
+    fn parquet_hash(&self) -> u64 {
+        parquet_hash(self.as_bytes())
+    }
+}
+
+impl<T: ParquetValue + ?Sized> ParquetValue for &T {
+    #[inline]
+    fn parquet_hash(&self) -> u64 {
+        (**self).parquet_hash()
+    }
+}
@@ -0,0 +1,504 @@
+use crate::sizing::ndv_for_fill_ratio;
+use crate::{Error, FilterFn, ParquetValue, ALIGNMENT, BUCKET_SIZE, MAX_NUM_BUCKETS};
+
+/// Read only split block bloom filter borrowing its bits from a byte slice.
+#[derive(Debug, Clone, Copy)]
+pub struct SbbfRef<'a> {
+    pub(crate) filter_fn: FilterFn,
+    pub(crate) buf: &'a [u8],
+    pub(crate) aligned: bool,
+}
+
+impl<'a> SbbfRef<'a> {
+    /// Creates a filter view over `buf`.
+    ///
+    /// Returns an error if length of `buf` isn't a non-zero multiple of [BUCKET_SIZE], if it has
+    /// more than [MAX_NUM_BUCKETS] buckets or if `buf` isn't aligned to [ALIGNMENT] bytes.
+    pub fn new(buf: &'a [u8]) -> Result<Self, Error> {
+        validate_len(buf)?;
+        if !is_aligned(buf) {
+            return Err(Error::Unaligned);
+        }
+        Ok(Self {
+            filter_fn: FilterFn::new(),
+            buf,
+            aligned: true,
+        })
+    }
+
+    /// Creates a filter view over `buf` which can have any alignment.
+    ///
+    /// Unaligned buffers are accessed using unaligned loads, which can be slower on some cpus.
+    ///
+    /// Returns an error if length of `buf` isn't a non-zero multiple of [BUCKET_SIZE] or if it
+    /// has more than [MAX_NUM_BUCKETS] buckets.
+    pub fn new_unaligned(buf: &'a [u8]) -> Result<Self, Error> {
+        validate_len(buf)?;
+        Ok(Self {
+            filter_fn: FilterFn::new(),
+            buf,
+            aligned: is_aligned(buf),
+        })
+    }
+
+    /// Check if filter contains `hash`.
+    #[inline(always)]
+    pub fn contains(&self, hash: u64) -> bool {
+        let num_buckets = self.num_buckets();
+        unsafe {
+            if self.aligned {
+                self.filter_fn
+                    .contains(self.buf.as_ptr(), num_buckets, hash)
+            } else {
+                self.filter_fn
+                    .contains_unaligned(self.buf.as_ptr(), num_buckets, hash)
+            }
+        }
+    }
+
+    /// Check if filter contains `value`, hashed by its parquet plain encoding.
+    #[inline]
+    pub fn contains_value<T: ParquetValue + ?Sized>(&self, value: &T) -> bool {
+        self.contains(value.parquet_hash())
+    }
+
+    /// Check if filter contains each hash in `hashes` and write the results into `out`.
+    /// See [FilterFn::contains_batch].
+    /// # Panics
+    /// Panics if `out` and `hashes` have different lengths.
+    #[inline]
+    pub fn contains_many(&self, hashes: &[u64], out: &mut [bool]) {
+        unsafe {
+            self.filter_fn
+                .contains_batch(self.buf.as_ptr(), self.num_buckets(), hashes, out)
+        }
+    }
+
+    /// Same as [SbbfRef::contains_many] but writes the results as a bitmask.
+    /// See [FilterFn::contains_batch_bitmask].
+    /// # Panics
+    /// Panics if `out` has less than `hashes.len().div_ceil(64)` elements.
+    #[inline]
+    pub fn contains_many_bitmask(&self, hashes: &[u64], out: &mut [u64]) {
+        unsafe {
+            self.filter_fn.contains_batch_bitmask(
+                self.buf.as_ptr(),
+                self.num_buckets(),
+                hashes,
+                out,
+            )
+        }
+    }
+
+    /// Returns true if `other` contains every hash this filter contains, which is when every bit
+    /// set in this filter is also set in `other`.
+    ///
+    /// Returns an error if the filters have different numbers of buckets.
+    pub fn is_subset_of(&self, other: SbbfRef<'_>) -> Result<bool, Error> {
+        let num_buckets = check_same_size(self.num_buckets(), other.num_buckets())?;
+        Ok(unsafe {
+            self.filter_fn
+                .is_subset_of(self.buf.as_ptr(), other.buf.as_ptr(), num_buckets)
+        })
+    }
+
+    /// Returns the number of bits that are set in the filter.
+    pub fn count_ones(&self) -> u64 {
+        unsafe {
+            self.filter_fn
+                .count_ones(self.buf.as_ptr(), self.num_buckets())
+        }
+    }
+
+    /// Returns the fraction of filter bits that are set.
+    pub fn fill_ratio(&self) -> f64 {
+        self.count_ones() as f64 / (self.buf.len() * 8) as f64
+    }
+
+    /// Estimates the number of distinct values that were inserted into the filter from
+    /// the fraction of bits that are set.
+    ///
+    /// Works for filters that were built elsewhere or merged with [SbbfMut::union], but values
+    /// that were inserted more than once are only counted once.
+    /// Returns [u64::MAX] if every bit is set.
+    pub fn estimate_ndv(&self) -> u64 {
+        round_ndv(ndv_for_fill_ratio(self.num_buckets(), self.fill_ratio()))
+    }
+
+    /// Returns the false positive probability of the filter, computed from the bits that are
+    /// actually set instead of the number of values it was sized for.
+    ///
+    /// A hash that isn't in the filter picks a random bucket and a random bit in each of its
+    /// words, so this is the average over buckets of the product of the fill ratios of its words.
+    pub fn estimated_fpp(&self) -> f64 {
+        let fpp: f64 = self
+            .buf
+            .chunks_exact(BUCKET_SIZE)
+            .map(|bucket| {
+                bucket
+                    .chunks_exact(4)
+                    .map(|word| {
+                        let word = u32::from_ne_bytes(word.try_into().unwrap());
+                        f64::from(word.count_ones()) / 32.0
+                    })
+                    .product::<f64>()
+            })
+            .sum();
+        fpp / self.num_buckets() as f64
+    }
+
+    /// Estimates the number of distinct values that were inserted into this filter or `other`,
+    /// from the bits that are set in the union of the filters.
+    ///
+    /// Returns [u64::MAX] if every bit is set in the union.
+    /// Returns an error if the filters have different numbers of buckets.
+    pub fn estimate_union_ndv(&self, other: SbbfRef<'_>) -> Result<u64, Error> {
+        let (union, _) = self.estimate_overlap(other)?;
+        Ok(round_ndv(union))
+    }
+
+    /// Estimates the number of distinct values that were inserted into both this filter
+    /// and `other`.
+    ///
+    /// The number of bits set in `self & other` is the sum of the bits set in each filter minus
+    /// the bits set in `self | other`, so this is the NDV of each filter minus the NDV of the union.
+    /// Error of the estimate is relative to the size of the union, so small intersections
+    /// of big sets are estimated poorly.
+    ///
+    /// If every bit is set in the union, returns the smaller NDV of the two filters.
+    /// Returns an error if the filters have different numbers of buckets.
+    pub fn estimate_intersection_ndv(&self, other: SbbfRef<'_>) -> Result<u64, Error> {
+        let (_, intersection) = self.estimate_overlap(other)?;
+        Ok(round_ndv(intersection))
+    }
+
+    /// Estimates the jaccard similarity of the sets of values that were inserted into this filter
+    /// and `other`, which is the size of their intersection divided by the size of their union.
+    /// See [SbbfRef::estimate_intersection_ndv].
+    ///
+    /// Returns 0 if both filters are empty and 1 if both filters have every bit set.
+    /// Returns an error if the filters have different numbers of buckets.
+    pub fn estimate_jaccard(&self, other: SbbfRef<'_>) -> Result<f64, Error> {
+        let (union, intersection) = self.estimate_overlap(other)?;
+        Ok(if union == 0.0 {
+            0.0
+        } else if intersection.is_infinite() {
+            1.0
+        } else {
+            intersection / union
+        })
+    }
+
+    /// Returns the estimated NDV of the union and of the intersection of the filters.
+    fn estimate_overlap(&self, other: SbbfRef<'_>) -> Result<(f64, f64), Error> {
+        let num_buckets = check_same_size(self.num_buckets(), other.num_buckets())?;
+        let num_bits = (self.buf.len() * 8) as f64;
+        let ndv = |ones: u64| ndv_for_fill_ratio(num_buckets, ones as f64 / num_bits);
+
+        let a = ndv(self.count_ones());
+        let b = ndv(other.count_ones());
+        let union = ndv(unsafe {
+            self.filter_fn
+                .count_ones_union(self.buf.as_ptr(), other.buf.as_ptr(), num_buckets)
+        });
+        // union has all the bits of both filters so it is never smaller than either of them
+        let intersection = if union.is_finite() {
+            (a + b - union).max(0.0)
+        } else {
+            a.min(b)
+        };
+        Ok((union, intersection))
+    }
+
+    /// Returns the filter bits.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.buf
+    }
+
+    /// Returns the number of buckets in the filter.
+    pub fn num_buckets(&self) -> usize {
+        self.buf.len() / BUCKET_SIZE
+    }
+}
+
+/// Split block bloom filter borrowing its bits from a mutable byte slice.
+#[derive(Debug)]
+pub struct SbbfMut<'a> {
+    pub(crate) filter_fn: FilterFn,
+    pub(crate) buf: &'a mut [u8],
+    pub(crate) aligned: bool,
+}
+
+impl<'a> SbbfMut<'a> {
+    /// Creates a mutable filter view over `buf`.
+    ///
+    /// Returns an error if length of `buf` isn't a non-zero multiple of [BUCKET_SIZE], if it has
+    /// more than [MAX_NUM_BUCKETS] buckets or if `buf` isn't aligned to [ALIGNMENT] bytes.
+    pub fn new(buf: &'a mut [u8]) -> Result<Self, Error> {
+        validate_len(buf)?;
+        if !is_aligned(buf) {
+            return Err(Error::Unaligned);
+        }
+        Ok(Self {
+            filter_fn: FilterFn::new(),
+            buf,
+            aligned: true,
+        })
+    }
+
+    /// Creates a mutable filter view over `buf` which can have any alignment.
+    ///
+    /// Unaligned buffers are accessed using unaligned loads and stores, which can be slower on some cpus.
+    ///
+    /// Returns an error if length of `buf` isn't a non-zero multiple of [BUCKET_SIZE] or if it
+    /// has more than [MAX_NUM_BUCKETS] buckets.
+    pub fn new_unaligned(buf: &'a mut [u8]) -> Result<Self, Error> {
+        validate_len(buf)?;
+        let aligned = is_aligned(buf);
+        Ok(Self {
+            filter_fn: FilterFn::new(),
+            buf,
+            aligned,
+        })
+    }
+
+    /// Check if filter contains `hash`.
+    #[inline(always)]
+    pub fn contains(&self, hash: u64) -> bool {
+        self.view().contains(hash)
+    }
+
+    /// Insert `hash` into the filter.
+    /// Returns true if `hash` was already in the filter.
+    #[inline(always)]
+    pub fn insert(&mut self, hash: u64) -> bool {
+        let num_buckets = self.num_buckets();
+        unsafe {
+            if self.aligned {
+                self.filter_fn
+                    .insert(self.buf.as_mut_ptr(), num_buckets, hash)
+            } else {
+                self.filter_fn
+                    .insert_unaligned(self.buf.as_mut_ptr(), num_buckets, hash)
+            }
+        }
+    }
+
+    /// Check if filter contains `value`, hashed by its parquet plain encoding.
+    #[inline]
+    pub fn contains_value<T: ParquetValue + ?Sized>(&self, value: &T) -> bool {
+        self.contains(value.parquet_hash())
+    }
+
+    /// Insert `value` into the filter, hashed by its parquet plain encoding.
+    /// Returns true if `value` was already in the filter.
+    #[inline]
+    pub fn insert_value<T: ParquetValue + ?Sized>(&mut self, value: &T) -> bool {
+        self.insert(value.parquet_hash())
+    }
+
+    /// Insert each hash in `hashes` into the filter.
+    /// Returns the number of hashes that were already in the filter, which is the same as
+    /// counting the `true` results of calling [SbbfMut::insert] in a loop.
+    ///
+    /// Hashes are partitioned by the region of the filter they fall into before inserting so
+    /// building a big filter mostly accesses memory sequentially.
+    /// Allocates a scratch buffer of up to 8 MiB.
+    #[cfg(feature = "alloc")]
+    pub fn insert_many(&mut self, hashes: &[u64]) -> usize {
+        let num_buckets = self.num_buckets();
+        unsafe {
+            crate::partition::insert_partitioned(
+                self.filter_fn,
+                self.buf.as_mut_ptr(),
+                num_buckets,
+                hashes,
+            )
+        }
+    }
+
+    /// Check if filter contains each hash in `hashes` and write the results into `out`.
+    /// See [SbbfRef::contains_many].
+    #[inline]
+    pub fn contains_many(&self, hashes: &[u64], out: &mut [bool]) {
+        self.view().contains_many(hashes, out)
+    }
+
+    /// Same as [SbbfMut::contains_many] but writes the results as a bitmask.
+    /// See [SbbfRef::contains_many_bitmask].
+    #[inline]
+    pub fn contains_many_bitmask(&self, hashes: &[u64], out: &mut [u64]) {
+        self.view().contains_many_bitmask(hashes, out)
+    }
+
+    /// Adds every hash `other` contains into this filter by setting the bits that are set in `other`.
+    /// See [FilterFn::union_into].
+    ///
+    /// Returns an error if the filters have different numbers of buckets.
+    pub fn union(&mut self, other: SbbfRef<'_>) -> Result<(), Error> {
+        let num_buckets = check_same_size(self.num_buckets(), other.num_buckets())?;
+        unsafe {
+            self.filter_fn
+                .union_into(self.buf.as_mut_ptr(), other.buf.as_ptr(), num_buckets)
+        };
+        Ok(())
+    }
+
+    /// Clears the bits that aren't set in `other`, so the filter contains the hashes both filters
+    /// contain. See [FilterFn::intersect_into].
+    ///
+    /// Returns an error if the filters have different numbers of buckets.
+    pub fn intersect(&mut self, other: SbbfRef<'_>) -> Result<(), Error> {
+        let num_buckets = check_same_size(self.num_buckets(), other.num_buckets())?;
+        unsafe {
+            self.filter_fn
+                .intersect_into(self.buf.as_mut_ptr(), other.buf.as_ptr(), num_buckets)
+        };
+        Ok(())
+    }
+
+    /// Returns true if `other` contains every hash this filter contains.
+    /// See [SbbfRef::is_subset_of].
+    pub fn is_subset_of(&self, other: SbbfRef<'_>) -> Result<bool, Error> {
+        self.view().is_subset_of(other)
+    }
+
+    /// Shrinks the filter by `factor` in place and returns a view over the folded filter,
+    /// which is at the start of the buffer. Bits after it are left as they were.
+    /// See [Sbbf::fold](crate::Sbbf::fold).
+    ///
+    /// Returns an error if `factor` is zero or doesn't divide the number of buckets.
+    pub fn fold(self, factor: usize) -> Result<SbbfMut<'a>, Error> {
+        let num_buckets = check_fold_factor(self.num_buckets(), factor)?;
+        let ptr = self.buf.as_mut_ptr();
+        unsafe { fold_buckets(ptr, ptr, num_buckets, factor) };
+        Ok(SbbfMut {
+            filter_fn: self.filter_fn,
+            buf: &mut self.buf[..num_buckets * BUCKET_SIZE],
+            aligned: self.aligned,
+        })
+    }
+
+    /// Returns the number of bits that are set in the filter.
+    pub fn count_ones(&self) -> u64 {
+        self.view().count_ones()
+    }
+
+    /// Returns the fraction of filter bits that are set.
+    pub fn fill_ratio(&self) -> f64 {
+        self.view().fill_ratio()
+    }
+
+    /// Estimates the number of distinct values that were inserted into the filter.
+    /// See [SbbfRef::estimate_ndv].
+    pub fn estimate_ndv(&self) -> u64 {
+        self.view().estimate_ndv()
+    }
+
+    /// Returns the false positive probability of the filter, computed from the bits that are set.
+    /// See [SbbfRef::estimated_fpp].
+    pub fn estimated_fpp(&self) -> f64 {
+        self.view().estimated_fpp()
+    }
+
+    /// Estimates the number of distinct values that were inserted into this filter or `other`.
+    /// See [SbbfRef::estimate_union_ndv].
+    pub fn estimate_union_ndv(&self, other: SbbfRef<'_>) -> Result<u64, Error> {
+        self.view().estimate_union_ndv(other)
+    }
+
+    /// Estimates the number of distinct values that were inserted into both this filter
+    /// and `other`.
+    /// See [SbbfRef::estimate_intersection_ndv].
+    pub fn estimate_intersection_ndv(&self, other: SbbfRef<'_>) -> Result<u64, Error> {
+        self.view().estimate_intersection_ndv(other)
+    }
+
+    /// Estimates the jaccard similarity of the values inserted into this filter and `other`.
+    /// See [SbbfRef::estimate_jaccard].
+    pub fn estimate_jaccard(&self, other: SbbfRef<'_>) -> Result<f64, Error> {
+        self.view().estimate_jaccard(other)
+    }
+
+    /// Returns a read only view of the filter.
+    pub fn view(&self) -> SbbfRef<'_> {
+        SbbfRef {
+            filter_fn: self.filter_fn,
+            buf: self.buf,
+            aligned: self.aligned,
+        }
+    }
+
+    /// Returns the filter bits.
+    pub fn as_bytes(&self) -> &[u8] {
+        self.buf
+    }
+
+    /// Returns the number of buckets in the filter.
+    pub fn num_buckets(&self) -> usize {
+        self.buf.len() / BUCKET_SIZE
+    }
+}
+
+fn validate_len(buf: &[u8]) -> Result<(), Error> {
+    if buf.is_empty() || !buf.len().is_multiple_of(BUCKET_SIZE) {
+        return Err(Error::InvalidLength(buf.len()));
+    }
+    let num_buckets = buf.len() / BUCKET_SIZE;
+    if num_buckets > MAX_NUM_BUCKETS {
+        return Err(Error::TooManyBuckets(num_buckets));
+    }
+    Ok(())
+}
+
+/// Returns the number of buckets after folding a filter with `num_buckets` buckets by `factor`.
+pub(crate) fn check_fold_factor(num_buckets: usize, factor: usize) -> Result<usize, Error> {
+    if factor == 0 || !num_buckets.is_multiple_of(factor) {
+        return Err(Error::InvalidFoldFactor(factor));
+    }
+    Ok(num_buckets / factor)
+}
+
+/// Writes the OR of each group of `factor` adjacent buckets in `src` into `dst`.
+///
+/// Bucket `i` of a filter with `n` buckets is `(hash_hi * n) >> 32` so bucket
+/// `(hash_hi * n / factor) >> 32` of the folded filter is `i / factor`, folded filter answers the
+/// same as a filter that was built with `n / factor` buckets.
+/// # Safety
+/// `src` should have `num_buckets * factor` buckets and `dst` should have `num_buckets` buckets.
+/// They can start at the same address since each bucket of `dst` is written after the buckets
+/// of `src` before it are read.
+pub(crate) unsafe fn fold_buckets(src: *const u8, dst: *mut u8, num_buckets: usize, factor: usize) {
+    const WORDS: usize = BUCKET_SIZE / 8;
+
+    let src = src as *const u64;
+    let dst = dst as *mut u64;
+    for i in 0..num_buckets {
+        let mut bucket = [0u64; WORDS];
+        for j in 0..factor {
+            let src = src.add((i * factor + j) * WORDS);
+            for (k, word) in bucket.iter_mut().enumerate() {
+                *word |= src.add(k).read_unaligned();
+            }
+        }
+        for (k, word) in bucket.iter().enumerate() {
+            dst.add(i * WORDS + k).write_unaligned(*word);
+        }
+    }
+}
+
+/// Rounds an NDV estimate, infinity is saturated to [u64::MAX].
+fn round_ndv(ndv: f64) -> u64 {
+    (ndv + 0.5) as u64
+}
+
+fn check_same_size(a: usize, b: usize) -> Result<usize, Error> {
+    if a != b {
+        return Err(Error::SizeMismatch(a, b));
+    }
+    Ok(a)
+}
+
+pub(crate) fn is_aligned(buf: &[u8]) -> bool {
+    (buf.as_ptr() as usize).is_multiple_of(ALIGNMENT)
+}
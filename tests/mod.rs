@@ -1,15 +1,24 @@
+use parquet_format_safe::thrift::protocol::{TCompactInputProtocol, TCompactOutputProtocol};
 use rand::Rng;
-use sbbf_rs::{FilterFn, ALIGNMENT, BUCKET_SIZE};
-use std::{
-    alloc::{alloc_zeroed, dealloc, Layout},
-    collections::HashSet,
+use sbbf_rs::parquet::{
+    decode_bloom_filter, encode_bloom_filter, BloomFilterHeader, MAX_HEADER_LEN,
 };
+use sbbf_rs::{
+    expected_fpp, fold_factor_for, num_bits_for, num_buckets_for, num_bytes_for,
+    num_bytes_for_pow2, parquet_hash, xxh64, ApproxDedupExt, AtomicSbbf, Backend, CountingSbbf,
+    Error, FilterFn, FixedLenDecimal, Int96, ParquetValue, RotatingSbbf, Sbbf, SbbfMut, SbbfRef,
+    ScalableSbbf, StaticFilterFn, ALIGNMENT, BUCKET_SIZE, MAX_NUM_BYTES, MIN_NUM_BYTES,
+};
+use std::collections::HashSet;
 use xxhash_rust::xxh3::xxh3_64;
 
+#[path = "../benches/parquet_impl.rs"]
+mod parquet_impl;
+
 fn run_test(bits_per_key: usize, max_fp: f64) {
     let num_keys = 1_000_000;
-    let mut filter = Filter::new(bits_per_key, num_keys);
-    let ref_filter = Filter::new(bits_per_key, num_keys);
+    let mut filter = new_filter(bits_per_key, num_keys);
+    let mut ref_filter = new_filter(bits_per_key, num_keys);
     let mut rng = rand::thread_rng();
 
     let mut hashes = HashSet::with_capacity(num_keys);
@@ -20,12 +29,7 @@ fn run_test(bits_per_key: usize, max_fp: f64) {
         let hash = xxh3_64(i.to_be_bytes().as_ref());
         filter.insert(hash);
         hashes.insert(hash);
-        parquet2::bloom_filter::insert(
-            unsafe {
-                std::slice::from_raw_parts_mut(ref_filter.buf.ptr, ref_filter.buf.layout.size())
-            },
-            hash,
-        );
+        parquet2::bloom_filter::insert(ref_filter.as_bytes_mut(), hash);
         assert!(filter.contains(hash));
     }
 
@@ -39,12 +43,9 @@ fn run_test(bits_per_key: usize, max_fp: f64) {
         let i = rng.gen_range(0..i + 1);
         let hash = xxh3_64(i.to_be_bytes().as_ref());
 
-        let ref_slice =
-            unsafe { std::slice::from_raw_parts(ref_filter.buf.ptr, ref_filter.buf.layout.size()) };
-
         assert_eq!(
             filter.contains(hash),
-            parquet2::bloom_filter::is_in_set(ref_slice, hash)
+            parquet2::bloom_filter::is_in_set(ref_filter.as_bytes(), hash)
         );
 
         if filter.contains(hash) {
@@ -67,12 +68,7 @@ fn run_test(bits_per_key: usize, max_fp: f64) {
     dbg!(fp_rate);
     assert!(fp_rate < max_fp);
 
-    let ref_slice =
-        unsafe { std::slice::from_raw_parts(ref_filter.buf.ptr, ref_filter.buf.layout.size()) };
-
-    let slice = unsafe { std::slice::from_raw_parts(filter.buf.ptr, filter.buf.layout.size()) };
-
-    if ref_slice != slice {
+    if ref_filter.as_bytes() != filter.as_bytes() {
         panic!("bytes don't match parquet2 filter");
     }
 }
@@ -86,53 +82,1160 @@ fn test_filter() {
     run_test(8, 0.02);
 }
 
-struct Filter {
-    filter_fn: FilterFn,
-    buf: Buf,
-    num_buckets: usize,
+fn new_filter(bits_per_key: usize, num_keys: usize) -> Sbbf {
+    let len = (bits_per_key / 8) * num_keys;
+    Sbbf::new((len + BUCKET_SIZE / 2) / BUCKET_SIZE)
+}
+
+#[test]
+fn test_sbbf() {
+    let mut filter = Sbbf::new(4);
+    assert_eq!(filter.num_buckets(), 4);
+    assert_eq!(filter.as_bytes().len(), 4 * BUCKET_SIZE);
+    assert!(filter.as_bytes().iter().all(|&b| b == 0));
+
+    assert!(!filter.insert(69));
+    assert!(filter.contains(69));
+    assert!(filter.insert(69));
+
+    let cloned = filter.clone();
+    assert_eq!(cloned, filter);
+    assert!(cloned.contains(69));
+
+    filter.insert(12);
+    assert_ne!(cloned, filter);
+    assert_ne!(Sbbf::new(4), Sbbf::new(8));
+}
+
+#[test]
+fn test_views() {
+    let mut filter = Sbbf::new(8);
+    let mut buf = filter.clone();
+
+    let mut view = SbbfMut::new(buf.as_bytes_mut()).unwrap();
+    assert_eq!(view.num_buckets(), 8);
+    for hash in 0..100u64 {
+        let hash = xxh3_64(&hash.to_le_bytes());
+        assert_eq!(view.insert(hash), filter.insert(hash));
+        assert!(view.contains(hash));
+    }
+    assert_eq!(view.as_bytes(), filter.as_bytes());
+    assert_eq!(view.view().as_bytes(), filter.view().as_bytes());
+
+    let view = SbbfRef::new(filter.as_bytes()).unwrap();
+    for hash in 0..1000u64 {
+        let hash = xxh3_64(&hash.to_le_bytes());
+        assert_eq!(view.contains(hash), filter.contains(hash));
+    }
+}
+
+#[test]
+fn test_view_validation() {
+    let mut filter = Sbbf::new(4);
+    let bytes = filter.as_bytes_mut();
+
+    assert_eq!(
+        SbbfRef::new(&bytes[..0]).unwrap_err(),
+        Error::InvalidLength(0)
+    );
+    assert_eq!(
+        SbbfRef::new(&bytes[..31]).unwrap_err(),
+        Error::InvalidLength(31)
+    );
+    assert_eq!(
+        SbbfRef::new(&bytes[BUCKET_SIZE..]).unwrap_err(),
+        Error::Unaligned
+    );
+    assert_eq!(
+        SbbfMut::new(&mut bytes[1..BUCKET_SIZE + 1]).unwrap_err(),
+        Error::Unaligned
+    );
+    assert!(SbbfRef::new(&bytes[ALIGNMENT..]).is_ok());
+    assert!(SbbfMut::new(&mut bytes[..BUCKET_SIZE]).is_ok());
+}
+
+#[test]
+#[cfg(target_pointer_width = "64")]
+#[should_panic(expected = "MAX_NUM_BUCKETS")]
+fn test_too_many_buckets() {
+    Sbbf::new(sbbf_rs::MAX_NUM_BUCKETS + 1);
+}
+
+#[test]
+fn test_unaligned_views() {
+    let num_buckets = 16;
+    let len = num_buckets * BUCKET_SIZE;
+    let mut filter = Sbbf::new(num_buckets);
+    let mut storage = Sbbf::new(num_buckets + 2 * ALIGNMENT / BUCKET_SIZE);
+
+    for offset in 0..ALIGNMENT {
+        filter.as_bytes_mut().fill(0);
+        let buf = &mut storage.as_bytes_mut()[offset..offset + len];
+        buf.fill(0);
+
+        assert!(offset == 0 || SbbfMut::new(buf).is_err());
+        let mut view = SbbfMut::new_unaligned(buf).unwrap();
+        for i in 0..500u64 {
+            let hash = xxh3_64(&i.to_le_bytes());
+            assert_eq!(view.insert(hash), filter.insert(hash));
+        }
+        assert_eq!(view.as_bytes(), filter.as_bytes());
+
+        let view = SbbfRef::new_unaligned(view.as_bytes()).unwrap();
+        for i in 0..5000u64 {
+            let hash = xxh3_64(&i.to_le_bytes());
+            assert_eq!(view.contains(hash), filter.contains(hash));
+        }
+    }
 }
 
-impl Filter {
-    fn new(bits_per_key: usize, num_keys: usize) -> Self {
-        let len = (bits_per_key / 8) * num_keys;
-        let len = ((len + BUCKET_SIZE / 2) / BUCKET_SIZE) * BUCKET_SIZE;
-        Self {
-            filter_fn: FilterFn::new(),
-            buf: Buf::new(len),
-            num_buckets: len / BUCKET_SIZE,
+#[test]
+fn test_sizing() {
+    for ndv in [0u64, 1, 10, 1000, 123_456, 10_000_000] {
+        for fpp in [0.5, 0.1, 0.01, 0.001, 1e-6] {
+            let expected = (-8.0 * ndv as f64 / (1.0 - f64::powf(fpp, 1.0 / 8.0)).ln()) as u64;
+            let bits = num_bits_for(ndv, fpp);
+            assert!(bits.abs_diff(expected) <= 1, "{} {}", bits, expected);
+
+            let bytes = num_bytes_for(ndv, fpp);
+            assert_eq!(bytes % BUCKET_SIZE, 0);
+            assert!((MIN_NUM_BYTES..=MAX_NUM_BYTES).contains(&bytes));
+            assert!(bytes as u64 * 8 >= bits.min(MAX_NUM_BYTES as u64 * 8));
+            assert_eq!(num_buckets_for(ndv, fpp), bytes / BUCKET_SIZE);
+
+            let pow2 = num_bytes_for_pow2(ndv, fpp);
+            assert!(pow2.is_power_of_two() && pow2 >= bytes && pow2 <= MAX_NUM_BYTES);
         }
     }
 
-    fn contains(&self, hash: u64) -> bool {
-        unsafe {
-            self.filter_fn
-                .contains(self.buf.ptr, self.num_buckets, hash)
+    assert_eq!(num_bytes_for(u64::MAX, 1e-9), MAX_NUM_BYTES);
+    assert_eq!(num_bytes_for(1, 0.5), MIN_NUM_BYTES);
+
+    const NUM_BUCKETS: usize = num_buckets_for(1000, 0.01);
+    assert_eq!(NUM_BUCKETS, num_buckets_for(1000, 0.01));
+}
+
+#[test]
+fn test_expected_fpp() {
+    assert_eq!(expected_fpp(10, 0), 0.0);
+    assert!(expected_fpp(1, u64::MAX) > 0.999);
+
+    for (ndv, fpp) in [(10_000u64, 0.01), (100_000, 0.001), (50_000, 0.1)] {
+        let num_buckets = num_buckets_for(ndv, fpp);
+        let expected = expected_fpp(num_buckets, ndv);
+        // parquet-mr sizing formula underestimates the split block fpp
+        assert!(
+            expected >= fpp && expected < fpp * 3.0,
+            "{} {}",
+            expected,
+            fpp
+        );
+
+        let mut filter = Sbbf::new(num_buckets);
+        for i in 0..ndv {
+            filter.insert(xxh3_64(&i.to_le_bytes()));
         }
+        let num_probes = 1_000_000u64;
+        let fp = (ndv..ndv + num_probes)
+            .filter(|i| filter.contains(xxh3_64(&i.to_le_bytes())))
+            .count();
+        let measured = fp as f64 / num_probes as f64;
+        assert!(
+            (measured - expected).abs() < expected * 0.1,
+            "{} {}",
+            measured,
+            expected
+        );
+    }
+}
+
+fn reference_header(num_bytes: i32) -> Vec<u8> {
+    use parquet_format_safe::{
+        BloomFilterAlgorithm, BloomFilterCompression, BloomFilterHash, SplitBlockAlgorithm,
+        Uncompressed, XxHash,
+    };
+
+    let header = parquet_format_safe::BloomFilterHeader::new(
+        num_bytes,
+        BloomFilterAlgorithm::BLOCK(SplitBlockAlgorithm::new()),
+        BloomFilterHash::XXHASH(XxHash::new()),
+        BloomFilterCompression::UNCOMPRESSED(Uncompressed::new()),
+    );
+    let mut out = Vec::new();
+    header
+        .write_to_out_protocol(&mut TCompactOutputProtocol::new(&mut out))
+        .unwrap();
+    out
+}
+
+#[test]
+fn test_parquet_header() {
+    for num_bytes in [0, 32, 63, 64, 1 << 13, 1 << 20, 128 * 1024 * 1024, i32::MAX] {
+        let expected = reference_header(num_bytes);
+
+        let header = BloomFilterHeader::new(num_bytes);
+        let mut buf = [0; MAX_HEADER_LEN];
+        let len = header.encode(&mut buf).unwrap();
+        assert_eq!(len, header.encoded_len());
+        assert_eq!(&buf[..len], expected.as_slice());
+
+        assert_eq!(BloomFilterHeader::decode(&expected).unwrap(), (header, len));
+
+        let mut input = expected.as_slice();
+        let decoded = parquet_format_safe::BloomFilterHeader::read_from_in_protocol(
+            &mut TCompactInputProtocol::new(&mut input, usize::MAX),
+        )
+        .unwrap();
+        assert_eq!(decoded.num_bytes, num_bytes);
+    }
+
+    assert_eq!(
+        BloomFilterHeader::new(1024).encode(&mut [0; 4]),
+        Err(Error::BufferTooSmall(16))
+    );
+}
+
+#[test]
+fn test_parquet_header_errors() {
+    let valid = reference_header(32);
+
+    for len in 0..valid.len() {
+        assert_eq!(
+            BloomFilterHeader::decode(&valid[..len]),
+            Err(Error::UnexpectedEof)
+        );
+    }
+
+    // algorithm union with field 2 set
+    let mut header = valid.clone();
+    header[3] = 2 << 4 | 12;
+    assert_eq!(
+        BloomFilterHeader::decode(&header),
+        Err(Error::UnsupportedAlgorithm(2))
+    );
+
+    // hash union with field 3 set
+    let mut header = valid.clone();
+    header[7] = 3 << 4 | 12;
+    assert_eq!(
+        BloomFilterHeader::decode(&header),
+        Err(Error::UnsupportedHash(3))
+    );
+
+    // compression union with field 2 set
+    let mut header = valid.clone();
+    header[11] = 2 << 4 | 12;
+    assert_eq!(
+        BloomFilterHeader::decode(&header),
+        Err(Error::UnsupportedCompression(2))
+    );
+
+    // missing compression
+    let mut header = valid[..10].to_vec();
+    header.push(0);
+    assert_eq!(
+        BloomFilterHeader::decode(&header),
+        Err(Error::InvalidHeader)
+    );
+
+    // negative size
+    let header = reference_header(-32);
+    assert_eq!(
+        BloomFilterHeader::decode(&header),
+        Err(Error::InvalidHeader)
+    );
+
+    // unknown fields are skipped
+    let mut header = valid[..valid.len() - 1].to_vec();
+    // field 5: binary "abc"
+    header.extend_from_slice(&[1 << 4 | 8, 3, b'a', b'b', b'c']);
+    // field 6: list of 2 structs, one with an i64 field
+    header.extend_from_slice(&[1 << 4 | 9, 2 << 4 | 12, 1 << 4 | 6, 0x80, 0x01, 0, 0]);
+    // field 7: map<i32, bool> with one entry
+    header.extend_from_slice(&[1 << 4 | 11, 1, 5 << 4 | 1, 2, 1]);
+    header.push(0);
+    assert_eq!(
+        BloomFilterHeader::decode(&header),
+        Ok((BloomFilterHeader::new(32), header.len()))
+    );
+}
+
+#[test]
+fn test_parquet_bloom_filter() {
+    let mut filter = Sbbf::new(num_buckets_for(1000, 0.01));
+    for i in 0..1000u64 {
+        filter.insert(xxh3_64(&i.to_le_bytes()));
+    }
+
+    let mut written = vec![0; MAX_HEADER_LEN + filter.as_bytes().len()];
+    let len = encode_bloom_filter(filter.as_bytes(), &mut written).unwrap();
+    assert_eq!(
+        encode_bloom_filter(filter.as_bytes(), &mut written[..len - 1]),
+        Err(Error::BufferTooSmall(len))
+    );
+    written.truncate(len);
+    written.extend_from_slice(b"trailing data");
+
+    #[cfg(feature = "std")]
+    {
+        let mut out = Vec::new();
+        sbbf_rs::parquet::write_bloom_filter(filter.as_bytes(), &mut out).unwrap();
+        assert_eq!(out, &written[..len]);
+    }
+
+    let (header, bitset) = decode_bloom_filter(&written).unwrap();
+    assert_eq!(header.num_bytes as usize, filter.as_bytes().len());
+    assert_eq!(bitset, filter.as_bytes());
+
+    let view = SbbfRef::new_unaligned(bitset).unwrap();
+    for i in 0..1000u64 {
+        assert!(view.contains(xxh3_64(&i.to_le_bytes())));
+    }
+
+    assert_eq!(
+        decode_bloom_filter(&written[..len - 1]),
+        Err(Error::UnexpectedEof)
+    );
+}
+
+#[test]
+fn test_xxh64() {
+    assert_eq!(xxh64(b"", 0), 0xef46db3751d8e999);
+    assert_eq!(xxh64(b"a", 0), 0xd24ec4f1a98c6e5b);
+    assert_eq!(xxh64(b"abc", 0), 0x44bc2cf5ad770999);
+
+    let mut rng = rand::thread_rng();
+    let mut input = vec![0u8; 300];
+    rng.fill(input.as_mut_slice());
+    for len in 0..input.len() {
+        let input = &input[..len];
+        assert_eq!(parquet_hash(input), xxhash_rust::xxh64::xxh64(input, 0));
+        let seed = rng.gen();
+        assert_eq!(xxh64(input, seed), xxhash_rust::xxh64::xxh64(input, seed));
+    }
+
+    const HASH: u64 = parquet_hash(b"abc");
+    assert_eq!(HASH, 0x44bc2cf5ad770999);
+}
+
+#[test]
+fn test_insert_value() {
+    let num_buckets = num_buckets_for(1000, 0.01);
+    let mut filter = Sbbf::new(num_buckets);
+    let mut reference = parquet_impl::Sbbf::new(&vec![0; num_buckets * BUCKET_SIZE]);
+
+    for i in 0..1000u64 {
+        let value = format!("value {}", i);
+        filter.insert_value(value.as_bytes());
+        assert!(filter.insert_value(value.as_bytes()));
+        reference.insert_hash(xxhash_rust::xxh64::xxh64(value.as_bytes(), 0));
     }
 
-    fn insert(&mut self, hash: u64) {
-        unsafe { self.filter_fn.insert(self.buf.ptr, self.num_buckets, hash) };
+    let mut copy = filter.clone();
+    let view = SbbfMut::new(copy.as_bytes_mut()).unwrap();
+    for i in 0..10_000u64 {
+        let value = format!("value {}", i);
+        let expected = reference.check_hash(xxhash_rust::xxh64::xxh64(value.as_bytes(), 0));
+        assert_eq!(filter.contains_value(value.as_bytes()), expected);
+        assert_eq!(filter.view().contains_value(value.as_bytes()), expected);
+        assert_eq!(view.contains_value(value.as_bytes()), expected);
     }
 }
 
-struct Buf {
-    ptr: *mut u8,
-    layout: Layout,
+#[test]
+fn test_contains_many() {
+    let mut rng = rand::thread_rng();
+
+    let mut filter = Sbbf::new(num_buckets_for(10_000, 0.05));
+    for _ in 0..10_000 {
+        filter.insert(rng.gen::<u64>() % 50_000);
+    }
+
+    let hashes = (0..1000)
+        .map(|_| rng.gen::<u64>() % 50_000)
+        .collect::<Vec<_>>();
+    let expected = hashes
+        .iter()
+        .map(|&h| filter.contains(h))
+        .collect::<Vec<_>>();
+
+    let mut out = vec![false; hashes.len()];
+    filter.contains_many(&hashes, &mut out);
+    assert_eq!(out, expected);
+
+    let mut bitmask = vec![0; hashes.len().div_ceil(64)];
+    filter.contains_many_bitmask(&hashes, &mut bitmask);
+    for (i, &expected) in expected.iter().enumerate() {
+        assert_eq!(bitmask[i / 64] >> (i % 64) & 1 == 1, expected);
+    }
+
+    let mut storage = filter.as_bytes().to_vec();
+    storage.insert(0, 0);
+    let view = SbbfMut::new_unaligned(&mut storage[1..]).unwrap();
+    let mut out = vec![false; hashes.len()];
+    view.contains_many(&hashes, &mut out);
+    assert_eq!(out, expected);
 }
 
-impl Buf {
-    fn new(len: usize) -> Self {
-        let layout = Layout::from_size_align(len, ALIGNMENT).unwrap();
-        let ptr = unsafe { alloc_zeroed(layout) };
+#[test]
+fn test_insert_many() {
+    let mut rng = rand::thread_rng();
+
+    // big enough to be partitioned
+    for num_buckets in [1, 100, 100_000] {
+        let hashes = (0..100_000)
+            .map(|_| rng.gen::<u64>() % 200_000)
+            .collect::<Vec<_>>();
+
+        let mut expected = Sbbf::new(num_buckets);
+        let mut expected_present = 0;
+        for &hash in hashes.iter() {
+            expected_present += expected.insert(hash) as usize;
+        }
+
+        let mut filter = Sbbf::new(num_buckets);
+        assert_eq!(filter.insert_many(&hashes), expected_present);
+        assert_eq!(filter, expected);
 
-        Self { layout, ptr }
+        let mut storage = vec![0; num_buckets * BUCKET_SIZE + 1];
+        let mut view = SbbfMut::new_unaligned(&mut storage[1..]).unwrap();
+        assert_eq!(view.insert_many(&hashes), expected_present);
+        assert_eq!(view.as_bytes(), expected.as_bytes());
+
+        assert_eq!(filter.insert_many(&hashes), hashes.len());
+        assert_eq!(filter.insert_many(&[]), 0);
     }
 }
 
-impl Drop for Buf {
-    fn drop(&mut self) {
-        unsafe {
-            dealloc(self.ptr, self.layout);
+#[test]
+fn test_batch_parquet2() {
+    println!("testing {}", FilterFn::new().which());
+
+    let mut rng = rand::thread_rng();
+
+    for num_keys in [10, 1000, 100_000] {
+        let mut filter = new_filter(16, num_keys);
+        let mut ref_filter = new_filter(16, num_keys);
+
+        let hashes = (0..num_keys).map(|_| rng.gen::<u64>()).collect::<Vec<_>>();
+        filter.insert_many(&hashes);
+        for &hash in hashes.iter() {
+            parquet2::bloom_filter::insert(ref_filter.as_bytes_mut(), hash);
+        }
+        assert_eq!(filter.as_bytes(), ref_filter.as_bytes());
+
+        let probes = hashes
+            .iter()
+            .copied()
+            .chain((0..num_keys).map(|_| rng.gen::<u64>()))
+            .collect::<Vec<_>>();
+        let mut out = vec![false; probes.len()];
+        filter.contains_many(&probes, &mut out);
+        for (&hash, &res) in probes.iter().zip(out.iter()) {
+            assert_eq!(
+                res,
+                parquet2::bloom_filter::is_in_set(ref_filter.as_bytes(), hash)
+            );
         }
     }
 }
+
+#[test]
+fn test_set_ops() {
+    let num_buckets = num_buckets_for(2000, 0.01);
+    let a = (0..1000u64).map(|i| i.parquet_hash()).collect::<Vec<_>>();
+    let b = (500..2000u64).map(|i| i.parquet_hash()).collect::<Vec<_>>();
+
+    let mut filter_a = Sbbf::new(num_buckets);
+    filter_a.insert_many(&a);
+    let mut filter_b = Sbbf::new(num_buckets);
+    filter_b.insert_many(&b);
+
+    let mut union = filter_a.clone();
+    union.union(filter_b.view()).unwrap();
+    let mut expected = Sbbf::new(num_buckets);
+    expected.insert_many(&a);
+    expected.insert_many(&b);
+    assert_eq!(union, expected);
+
+    let mut intersection = filter_a.clone();
+    intersection.intersect(filter_b.view()).unwrap();
+    for hash in a.iter().filter(|h| b.contains(h)) {
+        assert!(intersection.contains(*hash));
+    }
+
+    assert!(filter_a.is_subset_of(union.view()).unwrap());
+    assert!(filter_b.is_subset_of(union.view()).unwrap());
+    assert!(intersection.is_subset_of(filter_a.view()).unwrap());
+    assert!(!union.is_subset_of(filter_a.view()).unwrap());
+
+    let mut storage = vec![0; num_buckets * BUCKET_SIZE + 1];
+    let mut view = SbbfMut::new_unaligned(&mut storage[1..]).unwrap();
+    view.union(filter_a.view()).unwrap();
+    view.union(filter_b.view()).unwrap();
+    assert_eq!(view.as_bytes(), expected.as_bytes());
+    assert!(view.is_subset_of(union.view()).unwrap());
+
+    let other = Sbbf::new(num_buckets + 1);
+    assert_eq!(
+        union.union(other.view()).unwrap_err(),
+        Error::SizeMismatch(num_buckets, num_buckets + 1)
+    );
+    assert_eq!(
+        union.intersect(other.view()).unwrap_err(),
+        Error::SizeMismatch(num_buckets, num_buckets + 1)
+    );
+    assert_eq!(
+        union.is_subset_of(other.view()).unwrap_err(),
+        Error::SizeMismatch(num_buckets, num_buckets + 1)
+    );
+}
+
+#[test]
+fn test_fold() {
+    let hashes = (0..10_000u64).map(|i| i.parquet_hash()).collect::<Vec<_>>();
+
+    let num_buckets = 3 * 1024;
+    let mut filter = Sbbf::new(num_buckets);
+    filter.insert_many(&hashes);
+
+    for factor in [1, 2, 3, 4, 8, 16, 3 * 256, num_buckets] {
+        let folded = filter.fold(factor).unwrap();
+        assert_eq!(folded.num_buckets(), num_buckets / factor);
+
+        let mut expected = Sbbf::new(num_buckets / factor);
+        expected.insert_many(&hashes);
+        assert_eq!(folded, expected, "factor {}", factor);
+
+        let mut storage = vec![0; num_buckets * BUCKET_SIZE + 1];
+        storage[1..].copy_from_slice(filter.as_bytes());
+        let view = SbbfMut::new_unaligned(&mut storage[1..])
+            .unwrap()
+            .fold(factor)
+            .unwrap();
+        assert_eq!(view.as_bytes(), expected.as_bytes(), "factor {}", factor);
+    }
+
+    for factor in [0, 5, 2 * num_buckets] {
+        assert_eq!(
+            filter.fold(factor).unwrap_err(),
+            Error::InvalidFoldFactor(factor)
+        );
+    }
+}
+
+#[test]
+fn test_fold_factor_for() {
+    let num_buckets = num_buckets_for(1_000_000, 0.01);
+    assert_eq!(fold_factor_for(num_buckets, 1_000_000, 0.01), 1);
+    assert_eq!(fold_factor_for(3, 0, 0.01), 1);
+
+    let num_buckets = 1 << 16;
+    for ndv in [0, 100, 10_000, 1_000_000, 100_000_000] {
+        for fpp in [0.001, 0.01, 0.1] {
+            let factor = fold_factor_for(num_buckets, ndv, fpp);
+            assert!(factor.is_power_of_two());
+            if factor > 1 {
+                assert!(expected_fpp(num_buckets / factor, ndv) <= fpp);
+            }
+            if factor < num_buckets {
+                assert!(expected_fpp(num_buckets / factor / 2, ndv) > fpp);
+            }
+        }
+    }
+    assert_eq!(fold_factor_for(num_buckets, 0, 0.01), num_buckets);
+}
+
+#[test]
+fn test_estimates() {
+    let mut filter = Sbbf::new(num_buckets_for(100_000, 0.01));
+    assert_eq!(filter.count_ones(), 0);
+    assert_eq!(filter.fill_ratio(), 0.0);
+    assert_eq!(filter.estimate_ndv(), 0);
+    assert_eq!(filter.estimated_fpp(), 0.0);
+
+    let mut ndv = 0u64;
+    for target in [1_000u64, 10_000, 100_000, 400_000] {
+        let hashes = (ndv..target).map(|i| i.parquet_hash()).collect::<Vec<_>>();
+        filter.insert_many(&hashes);
+        // inserting the same values again doesn't change the estimates
+        filter.insert_many(&hashes[..hashes.len() / 2]);
+        ndv = target;
+
+        let estimate = filter.estimate_ndv() as f64;
+        assert!(
+            (estimate - ndv as f64).abs() < ndv as f64 * 0.03,
+            "ndv {} estimate {}",
+            ndv,
+            estimate
+        );
+
+        let fpp = filter.estimated_fpp();
+        let expected = expected_fpp(filter.num_buckets(), ndv);
+        assert!(
+            (fpp - expected).abs() < expected * 0.1 + 1e-6,
+            "ndv {} fpp {} expected {}",
+            ndv,
+            fpp,
+            expected
+        );
+
+        let num_probes = 200_000u64;
+        let false_positives = (0..num_probes)
+            .filter(|i| filter.contains((u64::MAX - i).parquet_hash()))
+            .count();
+        let measured = false_positives as f64 / num_probes as f64;
+        assert!(
+            (measured - fpp).abs() < fpp * 0.15 + 0.0005,
+            "ndv {} measured {} estimated {}",
+            ndv,
+            measured,
+            fpp
+        );
+
+        let ones = filter
+            .as_bytes()
+            .iter()
+            .map(|b| u64::from(b.count_ones()))
+            .sum::<u64>();
+        assert_eq!(filter.count_ones(), ones);
+        assert_eq!(
+            filter.fill_ratio(),
+            ones as f64 / (filter.num_buckets() * BUCKET_SIZE * 8) as f64
+        );
+        assert_eq!(filter.view().estimate_ndv(), filter.estimate_ndv());
+    }
+
+    filter.as_bytes_mut().fill(0xff);
+    assert_eq!(filter.fill_ratio(), 1.0);
+    assert_eq!(filter.estimate_ndv(), u64::MAX);
+    assert_eq!(filter.estimated_fpp(), 1.0);
+}
+
+#[test]
+fn test_overlap_estimates() {
+    let num_buckets = num_buckets_for(100_000, 0.01);
+    let filter_of = |values: std::ops::Range<u64>| {
+        let mut filter = Sbbf::new(num_buckets);
+        let hashes = values.map(|i| i.parquet_hash()).collect::<Vec<_>>();
+        filter.insert_many(&hashes);
+        filter
+    };
+
+    let size = 50_000u64;
+    for overlap in [0, 5_000, 25_000, 45_000, size] {
+        let a = filter_of(0..size);
+        let b = filter_of(size - overlap..2 * size - overlap);
+        let union = (2 * size - overlap) as f64;
+        let jaccard = overlap as f64 / union;
+
+        let est_union = a.estimate_union_ndv(b.view()).unwrap() as f64;
+        assert!(
+            (est_union - union).abs() < union * 0.03,
+            "overlap {} union {}",
+            overlap,
+            est_union
+        );
+        // error is relative to the union since it is the difference of the estimates
+        let est_overlap = a.estimate_intersection_ndv(b.view()).unwrap() as f64;
+        assert!(
+            (est_overlap - overlap as f64).abs() < union * 0.03,
+            "overlap {} estimate {}",
+            overlap,
+            est_overlap
+        );
+        let est_jaccard = a.estimate_jaccard(b.view()).unwrap();
+        assert!(
+            (est_jaccard - jaccard).abs() < 0.03,
+            "overlap {} jaccard {} estimate {}",
+            overlap,
+            jaccard,
+            est_jaccard
+        );
+        assert!((0.0..=1.0).contains(&est_jaccard));
+
+        assert_eq!(
+            b.estimate_intersection_ndv(a.view()).unwrap(),
+            a.estimate_intersection_ndv(b.view()).unwrap()
+        );
+        let mut merged = a.clone();
+        merged.union(b.view()).unwrap();
+        assert_eq!(
+            a.estimate_union_ndv(b.view()).unwrap(),
+            merged.estimate_ndv()
+        );
+    }
+
+    let a = filter_of(0..size);
+    assert_eq!(a.estimate_jaccard(a.view()).unwrap(), 1.0);
+    assert_eq!(
+        a.estimate_intersection_ndv(a.view()).unwrap(),
+        a.estimate_ndv()
+    );
+
+    let empty = Sbbf::new(num_buckets);
+    assert_eq!(empty.estimate_jaccard(empty.view()).unwrap(), 0.0);
+    assert_eq!(a.estimate_intersection_ndv(empty.view()).unwrap(), 0);
+    assert_eq!(a.estimate_jaccard(empty.view()).unwrap(), 0.0);
+
+    let mut full = Sbbf::new(num_buckets);
+    full.as_bytes_mut().fill(0xff);
+    assert_eq!(full.estimate_union_ndv(a.view()).unwrap(), u64::MAX);
+    assert_eq!(
+        full.estimate_intersection_ndv(a.view()).unwrap(),
+        a.estimate_ndv()
+    );
+    assert_eq!(full.estimate_jaccard(full.view()).unwrap(), 1.0);
+
+    assert_eq!(
+        a.estimate_jaccard(Sbbf::new(num_buckets + 1).view())
+            .unwrap_err(),
+        Error::SizeMismatch(num_buckets, num_buckets + 1)
+    );
+}
+
+#[test]
+fn test_atomic_sbbf() {
+    const NUM_THREADS: usize = 8;
+
+    let num_buckets = num_buckets_for(100_000, 0.01);
+    // threads insert overlapping ranges so the same buckets are hit concurrently
+    let hashes = (0..100_000u64)
+        .map(|i| xxh3_64(&i.to_le_bytes()))
+        .collect::<Vec<_>>();
+
+    let mut expected = Sbbf::new(num_buckets);
+    expected.insert_many(&hashes);
+
+    for _ in 0..4 {
+        let filter = AtomicSbbf::new(num_buckets);
+        std::thread::scope(|s| {
+            for t in 0..NUM_THREADS {
+                let filter = &filter;
+                let hashes = &hashes;
+                s.spawn(move || {
+                    let start = t * hashes.len() / NUM_THREADS;
+                    for &hash in hashes[start..].iter().chain(&hashes[..start]) {
+                        filter.insert(hash);
+                        assert!(filter.contains(hash));
+                    }
+                });
+            }
+        });
+
+        assert_eq!(filter.to_sbbf(), expected);
+        for &hash in hashes.iter() {
+            assert!(filter.contains(hash));
+            assert!(filter.insert(hash));
+        }
+    }
+
+    let filter = AtomicSbbf::from(&expected);
+    assert_eq!(filter.num_buckets(), num_buckets);
+    assert_eq!(Sbbf::from(&filter), expected);
+    for i in 0..1000u64 {
+        assert_eq!(filter.contains_value(&i), expected.contains_value(&i));
+    }
+}
+
+#[test]
+fn test_counting_sbbf() {
+    let hashes = (0..10_000u64).map(|i| i.parquet_hash()).collect::<Vec<_>>();
+    let (kept, removed) = hashes.split_at(hashes.len() / 2);
+    let num_buckets = num_buckets_for(hashes.len() as u64, 0.01);
+
+    let mut filter = CountingSbbf::new(num_buckets);
+    let mut expected = Sbbf::new(num_buckets);
+    for &hash in &hashes {
+        assert_eq!(filter.insert(hash), expected.insert(hash));
+    }
+    assert_eq!(filter.to_sbbf(), expected);
+    assert!(hashes.iter().all(|&hash| filter.contains(hash)));
+
+    for &hash in removed {
+        assert!(filter.remove(hash));
+    }
+    let mut expected = Sbbf::new(num_buckets);
+    expected.insert_many(kept);
+    assert_eq!(Sbbf::from(&filter), expected);
+    assert!(kept.iter().all(|&hash| filter.contains(hash)));
+    assert_eq!(
+        removed
+            .iter()
+            .filter(|&&hash| filter.contains(hash))
+            .count(),
+        removed
+            .iter()
+            .filter(|&&hash| expected.contains(hash))
+            .count()
+    );
+
+    for &hash in kept {
+        assert!(filter.remove(hash));
+    }
+    assert_eq!(filter, CountingSbbf::new(num_buckets));
+    assert!(!filter.remove(hashes[0]));
+
+    // saturated counters are never decremented
+    let mut filter = CountingSbbf::new(1);
+    for _ in 0..20 {
+        filter.insert_value(&42i64);
+    }
+    filter.insert_value(&43i64);
+    for _ in 0..20 {
+        assert!(filter.remove_value(&42i64));
+    }
+    assert!(filter.contains_value(&42i64));
+    assert!(filter.remove_value(&43i64));
+    assert!(filter.contains_value(&42i64));
+}
+
+#[test]
+fn test_scalable_sbbf() {
+    let fpp = 0.01;
+    let hashes = (0..500_000u64)
+        .map(|i| i.parquet_hash())
+        .collect::<Vec<_>>();
+
+    let mut filter = ScalableSbbf::new(1_000, fpp);
+    assert_eq!(filter.stages().len(), 1);
+    let num_duplicates = hashes.iter().filter(|&&hash| filter.insert(hash)).count();
+    // false positives of the earlier stages are counted as duplicates
+    assert!(num_duplicates < (hashes.len() as f64 * fpp) as usize);
+    assert!(hashes.iter().all(|&hash| filter.contains(hash)));
+    assert!(filter.insert(hashes[0]));
+
+    let num_stages = filter.stages().len();
+    assert!((8..=10).contains(&num_stages), "{}", num_stages);
+    for stages in filter.stages().windows(2) {
+        assert!(stages[1].num_buckets() > stages[0].num_buckets());
+    }
+
+    let num_probes = 200_000u64;
+    let measure_fpp = |filter: &ScalableSbbf| {
+        let false_positives = (0..num_probes)
+            .filter(|i| filter.contains((u64::MAX - i).parquet_hash()))
+            .count();
+        false_positives as f64 / num_probes as f64
+    };
+    let measured = measure_fpp(&filter);
+    assert!(measured < fpp, "{}", measured);
+    assert!(filter.estimated_fpp() < fpp, "{}", filter.estimated_fpp());
+    assert!((measured - filter.estimated_fpp()).abs() < 0.002);
+
+    filter.compact(&hashes);
+    assert_eq!(filter.stages().len(), 1);
+    assert!(hashes.iter().all(|&hash| filter.contains(hash)));
+    let measured = measure_fpp(&filter);
+    assert!(measured < fpp, "{}", measured);
+
+    // keeps growing after compacting
+    for i in 500_000..1_000_000u64 {
+        filter.insert_value(&i);
+    }
+    assert!(filter.stages().len() > 1);
+    assert!((0..1_000_000u64).all(|i| filter.contains_value(&i)));
+    assert!(measure_fpp(&filter) < fpp);
+}
+
+#[test]
+fn test_rotating_sbbf() {
+    let batch = |start: u64| {
+        (start..start + 2_000)
+            .map(|i| i.parquet_hash())
+            .collect::<Vec<_>>()
+    };
+    let (a, b, c) = (batch(0), batch(10_000), batch(20_000));
+    let count_contained = |filter: &RotatingSbbf, hashes: &[u64]| {
+        hashes.iter().filter(|&&h| filter.contains(h)).count()
+    };
+
+    let mut filter = RotatingSbbf::new(num_buckets_for(4_000, 0.001), 3);
+    let mut num_new = 0;
+    for hashes in [&a, &b, &c] {
+        if hashes != &a {
+            filter.rotate();
+        }
+        num_new += hashes.iter().filter(|&&h| filter.insert_if_new(h)).count();
+    }
+    assert!(num_new > 6_000 - 30, "{}", num_new);
+    for hashes in [&a, &b, &c] {
+        assert_eq!(count_contained(&filter, hashes), hashes.len());
+    }
+
+    // seeing `a` again moves it to the newest generation
+    assert!(a.iter().all(|&h| !filter.insert_if_new(h)));
+    assert!(a.iter().all(|&h| filter.generation(0).contains(h)));
+
+    let probes = batch(1 << 40);
+    for hash in probes.iter().chain(&a).chain(&b) {
+        let expected = (0..3).any(|age| filter.generation(age).contains(*hash));
+        assert_eq!(filter.contains(*hash), expected);
+    }
+
+    filter.rotate();
+    assert_eq!(count_contained(&filter, &a), a.len());
+    assert_eq!(count_contained(&filter, &b), b.len());
+    assert_eq!(filter.generation(0).count_ones(), 0);
+
+    filter.rotate();
+    assert!(count_contained(&filter, &b) < 10);
+    assert_eq!(count_contained(&filter, &a), a.len());
+    assert!(b.iter().filter(|&&h| filter.insert_if_new(h)).count() > b.len() - 10);
+
+    filter.rotate();
+    assert!(count_contained(&filter, &a) < 10);
+    assert_eq!(count_contained(&filter, &b), b.len());
+
+    let mut filter = RotatingSbbf::new(1, 1);
+    assert!(filter.insert_value_if_new("x"));
+    assert!(!filter.insert_value_if_new("x"));
+    filter.rotate();
+    assert!(!filter.contains_value("x"));
+}
+
+#[test]
+fn test_approx_dedup() {
+    let values = (0..20_000u64).chain(0..10_000).chain(15_000..25_000);
+
+    let mut dedup = values.approx_dedup(Sbbf::for_ndv(25_000, 0.001), |v| v.parquet_hash());
+    let deduped = dedup.by_ref().collect::<Vec<_>>();
+    // false positives can drop a few values that weren't seen before
+    assert!(deduped.len() <= 25_000 && deduped.len() > 25_000 - 50);
+    assert_eq!(deduped.len() as u64 + dedup.num_suppressed(), 40_000);
+    assert_eq!(deduped.iter().collect::<HashSet<_>>().len(), deduped.len());
+    assert!(deduped.windows(2).all(|w| w[0] < w[1]));
+
+    let filter = dedup.into_filter();
+    assert!((0..25_000u64).all(|v| filter.contains_value(&v)));
+    let mut dedup = (0..10u64).approx_dedup(filter, |v| v.parquet_hash());
+    assert_eq!(dedup.next(), None);
+    assert_eq!(dedup.num_suppressed(), 10);
+
+    let words = ["a", "b", "a", "c", "b"];
+    let deduped = words
+        .iter()
+        .approx_dedup(Sbbf::new(1), |w| w.parquet_hash())
+        .collect::<Vec<_>>();
+    assert_eq!(deduped, [&"a", &"b", &"c"]);
+}
+
+#[test]
+fn test_backends() {
+    let available = FilterFn::available_backends().collect::<Vec<_>>();
+    assert_eq!(available.first(), Some(&FilterFn::new().backend()));
+    assert_eq!(available.last(), Some(&Backend::Fallback));
+
+    const NUM_BUCKETS: usize = 64;
+    let mut reference = parquet_impl::Sbbf::new(&[0; NUM_BUCKETS * BUCKET_SIZE]);
+    for i in 0..1_000u64 {
+        reference.insert_hash(i.parquet_hash());
+    }
+
+    for backend in available.iter().copied() {
+        let filter_fn = FilterFn::with_backend(backend).unwrap();
+        assert_eq!(filter_fn.backend(), backend);
+        assert_eq!(filter_fn.which(), backend.name());
+        assert_eq!(backend.to_string(), backend.name());
+        assert_eq!(filter_fn.self_test(), Ok(()));
+
+        let mut buf = vec![0u8; NUM_BUCKETS * BUCKET_SIZE];
+        for i in 0..1_000u64 {
+            unsafe { filter_fn.insert_unaligned(buf.as_mut_ptr(), NUM_BUCKETS, i.parquet_hash()) };
+        }
+        for i in 0..10_000u64 {
+            let hash = i.parquet_hash();
+            let res = unsafe { filter_fn.contains_unaligned(buf.as_ptr(), NUM_BUCKETS, hash) };
+            assert_eq!(res, reference.check_hash(hash), "{}", backend);
+        }
+    }
+
+    for backend in [
+        Backend::Avx512,
+        Backend::Avx2,
+        Backend::Sse41,
+        Backend::Neon,
+        Backend::Wasm,
+        Backend::Rvv,
+        Backend::Fallback,
+    ] {
+        assert_eq!(
+            FilterFn::with_backend(backend).is_some(),
+            available.contains(&backend)
+        );
+    }
+    #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+    {
+        assert!(FilterFn::with_backend(Backend::Neon).is_none());
+        assert_eq!(
+            FilterFn::with_backend(Backend::Avx2).is_some(),
+            is_x86_feature_detected!("avx2")
+        );
+    }
+}
+
+#[test]
+fn test_static_filter_fn() {
+    let static_fn = StaticFilterFn;
+    assert!(FilterFn::available_backends().any(|backend| backend == static_fn.backend()));
+    let filter_fn = FilterFn::with_backend(static_fn.backend()).unwrap();
+
+    const NUM_BUCKETS: usize = 64;
+    let mut expected = Sbbf::new(NUM_BUCKETS);
+    let mut filter = Sbbf::new(NUM_BUCKETS);
+    for i in 0..1_000u64 {
+        let hash = i.parquet_hash();
+        let res =
+            unsafe { filter_fn.insert(expected.as_bytes_mut().as_mut_ptr(), NUM_BUCKETS, hash) };
+        let static_res =
+            unsafe { static_fn.insert(filter.as_bytes_mut().as_mut_ptr(), NUM_BUCKETS, hash) };
+        assert_eq!(res, static_res);
+    }
+    assert_eq!(filter, expected);
+
+    let hashes = (0..10_000u64).map(|i| i.parquet_hash()).collect::<Vec<_>>();
+    let mut out = vec![false; hashes.len()];
+    unsafe { static_fn.contains_batch(filter.as_bytes().as_ptr(), NUM_BUCKETS, &hashes, &mut out) };
+    for (&hash, &res) in hashes.iter().zip(&out) {
+        assert_eq!(res, expected.contains(hash));
+        assert_eq!(res, unsafe {
+            static_fn.contains(filter.as_bytes().as_ptr(), NUM_BUCKETS, hash)
+        });
+    }
+}
+
+#[cfg(feature = "futures")]
+#[test]
+fn test_approx_dedup_stream() {
+    use futures::executor::block_on;
+    use futures::stream::{self, StreamExt};
+    use sbbf_rs::ApproxDedupStreamExt;
+
+    let values = (0..2_000u64).chain(500..1_500);
+    let mut dedup = ApproxDedupStreamExt::approx_dedup(
+        stream::iter(values.clone()),
+        Sbbf::for_ndv(2_000, 0.001),
+        |v| v.parquet_hash(),
+    );
+    let deduped = block_on(dedup.by_ref().collect::<Vec<_>>());
+    let expected = values
+        .approx_dedup(Sbbf::for_ndv(2_000, 0.001), |v| v.parquet_hash())
+        .collect::<Vec<_>>();
+    assert_eq!(deduped, expected);
+    assert_eq!(deduped.len() as u64 + dedup.num_suppressed(), 3_000);
+    assert!(dedup.num_suppressed() >= 1_000);
+}
+
+#[test]
+fn test_backends_dedup() {
+    // every backend has to drop exactly the items the fallback drops, tiny filters make most
+    // inserts find some of their bits already set
+    let values = (0..3_000u64).chain(1_000..2_000);
+    for num_buckets in [1, 2, num_buckets_for(3_000, 0.01)] {
+        let dedup = |backend| {
+            let filter = Sbbf::with_backend(num_buckets, backend).unwrap();
+            let mut dedup = values.clone().approx_dedup(filter, |v| v.parquet_hash());
+            let deduped = dedup.by_ref().collect::<Vec<_>>();
+            (deduped, dedup.num_suppressed())
+        };
+        let expected = dedup(Backend::Fallback);
+        for backend in FilterFn::available_backends() {
+            assert_eq!(dedup(backend), expected, "{} {}", backend, num_buckets);
+        }
+    }
+    assert!(Sbbf::with_backend(1, Backend::Fallback).is_some());
+
+    // same for the results of RotatingSbbf::insert_if_new across rotations
+    let hashes = (0..2_000u64)
+        .map(|i| (i % 1_500).parquet_hash())
+        .collect::<Vec<_>>();
+    for num_buckets in [1, 2, num_buckets_for(1_500, 0.01)] {
+        let insert_if_new = |backend| {
+            let mut filter = RotatingSbbf::with_backend(num_buckets, 3, backend).unwrap();
+            let mut res = Vec::new();
+            for chunk in hashes.chunks(500) {
+                res.extend(chunk.iter().map(|&h| filter.insert_if_new(h)));
+                filter.rotate();
+            }
+            res
+        };
+        let expected = insert_if_new(Backend::Fallback);
+        for backend in FilterFn::available_backends() {
+            assert_eq!(
+                insert_if_new(backend),
+                expected,
+                "{} {}",
+                backend,
+                num_buckets
+            );
+        }
+    }
+}
+
+#[test]
+#[should_panic]
+fn test_contains_many_len_mismatch() {
+    let filter = Sbbf::new(1);
+    filter.contains_many(&[1, 2, 3], &mut [false; 2]);
+}
+
+#[test]
+fn test_parquet_value() {
+    use parquet2::bloom_filter::{hash_byte, hash_native};
+
+    let mut rng = rand::thread_rng();
+    for _ in 0..1000 {
+        let v: i32 = rng.gen();
+        assert_eq!(v.parquet_hash(), hash_native(v));
+        assert_eq!((v as u32).parquet_hash(), hash_native(v));
+        assert_eq!((v as i16).parquet_hash(), hash_native(v as i16 as i32));
+        assert_eq!((v as u16).parquet_hash(), hash_native(v as u16 as i32));
+        assert_eq!((v as i8).parquet_hash(), hash_native(v as i8 as i32));
+        assert_eq!((v as u8).parquet_hash(), hash_native(v as u8 as i32));
+
+        let v: i64 = rng.gen();
+        assert_eq!(v.parquet_hash(), hash_native(v));
+        assert_eq!((v as u64).parquet_hash(), hash_native(v));
+
+        let v: f32 = rng.gen();
+        assert_eq!(v.parquet_hash(), hash_native(v));
+        let v: f64 = rng.gen();
+        assert_eq!(v.parquet_hash(), hash_native(v));
+
+        let v: [u32; 3] = rng.gen();
+        assert_eq!(Int96(v).parquet_hash(), hash_native(v));
+
+        let v: [u8; 16] = rng.gen();
+        assert_eq!(v.parquet_hash(), hash_byte(v));
+        assert_eq!(v.as_slice().parquet_hash(), hash_byte(v));
+        assert_eq!(v.to_vec().parquet_hash(), hash_byte(v));
+
+        let v = format!("{}", rng.gen::<u64>());
+        assert_eq!(v.parquet_hash(), hash_byte(&v));
+        assert_eq!(v.as_str().parquet_hash(), hash_byte(&v));
+    }
+
+    assert_ne!(0.0f64.parquet_hash(), (-0.0f64).parquet_hash());
+    assert_eq!(true.parquet_hash(), hash_byte([1]));
+    assert_eq!(false.parquet_hash(), hash_byte([0]));
+    assert_eq!(
+        FixedLenDecimal::<4>(12345).parquet_hash(),
+        hash_byte([0, 0, 0x30, 0x39])
+    );
+    assert_eq!(
+        FixedLenDecimal::<5>(-2).parquet_hash(),
+        hash_byte([0xff, 0xff, 0xff, 0xff, 0xfe])
+    );
+    assert_eq!(
+        FixedLenDecimal::<16>(i128::MIN).parquet_hash(),
+        hash_byte(i128::MIN.to_be_bytes())
+    );
+
+    let mut filter = Sbbf::new(16);
+    assert!(!filter.insert_value(&42i32));
+    assert!(filter.contains(hash_native(42i32)));
+    assert!(filter.contains_value("hello") == filter.contains(hash_byte("hello")));
+    assert!(!filter.insert_value("hello"));
+    assert!(filter.contains_value(&String::from("hello")));
+    assert!(filter.contains_value(b"hello"));
+}